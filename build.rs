@@ -1,37 +1,28 @@
-use std::env;
-use std::path::PathBuf;
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     tonic_build::compile_protos("proto/nodeMessage.proto")?;
+    println!("cargo:rerun-if-changed=cuda_mining_lib");
+
+    // the CPU reference of the mining kernel is always built, so the kernel interface is tested without a GPU
+    cc::Build::new()
+        .file("./cuda_mining_lib/src/mining_cpu.c")
+        .include("./cuda_mining_lib/include")
+        .compile("mining_cpu");
+
     // if no CUDA installed, return
     if !cfg!(feature = "cuda") {
         return Ok(());
     }
 
-    println!("cargo:rustc-link-search=native=/cuda_mining_lib/include/mining.h");
-    println!("cargo:rustc-link-lib=static=mining_lib");
     let cuda_include_path = "/usr/local/cuda/include";
     // Compile the C++/CUDA code using the `cc` crate
     cc::Build::new()
         .cuda(true)
         .file("./cuda_mining_lib/src/mining.cu")
+        .include("./cuda_mining_lib/include")
         .include(cuda_include_path) // Add the CUDA include path to the build
         .compile("mining_lib");
 
-    let bindings = bindgen::Builder::default()
-        .header("./cuda_mining_lib/include/mining.h")
-        .clang_arg("-x") // Specify that this is C++
-        .clang_arg("c++")
-        .clang_arg(format!("-I{}", cuda_include_path))
-        // Tell cargo to invalidate the built crate whenever the wrapper changes
-        .generate()
-        .expect("Unable to generate bindings");
-
-    // Write the bindings to the $OUT_DIR/bindings.rs file.
-    let out_path = PathBuf::from(env::var("OUT_DIR").unwrap());
-    bindings
-        .write_to_file(out_path.join("bindings.rs"))
-        .expect("Couldn't write bindings!");
-
+    println!("cargo:rustc-link-search=native=/usr/local/cuda/lib64");
     println!("cargo:rustc-link-lib=dylib=cudart");
     Ok(())
 }
@@ -0,0 +1,32 @@
+/*
+ * Mining kernel interface
+ * A kernel searches the nonces from start_nonce to max_nonce - 1 for the lowest nonce where
+ * sha256(block_data + decimal nonce) starts with `difficulty` zero hex digits.
+ * On success the nonce is written to final_nonce and the 32 bytes of the hash to final_hash,
+ * otherwise final_nonce is MINING_NO_NONCE, or MINING_GPU_ERROR if the GPU could not run the search.
+ * A long search is split into ranges of nonces, so the caller can stop between them.
+ */
+#ifndef MINING_H
+#define MINING_H
+
+#define MINING_NO_NONCE -1
+#define MINING_GPU_ERROR -2
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* The GPU kernel, only built with the cuda feature */
+void mine_block(const char* block_data, int data_len, int difficulty,
+                unsigned char* final_hash, int* final_nonce, int start_nonce, int max_nonce);
+
+/* The CPU reference of the GPU kernel, always built */
+void mine_block_cpu(const char* block_data, int data_len, int difficulty,
+                    unsigned char* final_hash, int* final_nonce, int start_nonce,
+                    int max_nonce);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
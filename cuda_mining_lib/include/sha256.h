@@ -0,0 +1,155 @@
+/*
+ * SHA-256 and the nonce check shared by the GPU kernel and its CPU reference
+ * The functions run on the host and, when compiled by nvcc, on the device.
+ */
+#ifndef MINING_SHA256_H
+#define MINING_SHA256_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __CUDACC__
+#define MINING_HD __host__ __device__
+#else
+#define MINING_HD
+#endif
+
+typedef struct {
+    uint32_t state[8];
+    uint64_t length;
+    uint8_t buffer[64];
+    size_t buffered;
+} sha256_ctx;
+
+#define ROTR(x, n) (((x) >> (n)) | ((x) << (32 - (n))))
+
+MINING_HD static inline void sha256_transform(uint32_t state[8], const uint8_t block[64]) {
+    const uint32_t k[64] = {
+        0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+        0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+        0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+        0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+        0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+        0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+        0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+        0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+    };
+    uint32_t w[64];
+    for (int i = 0; i < 16; i++) {
+        w[i] = ((uint32_t)block[i * 4] << 24) | ((uint32_t)block[i * 4 + 1] << 16) |
+               ((uint32_t)block[i * 4 + 2] << 8) | (uint32_t)block[i * 4 + 3];
+    }
+    for (int i = 16; i < 64; i++) {
+        uint32_t s0 = ROTR(w[i - 15], 7) ^ ROTR(w[i - 15], 18) ^ (w[i - 15] >> 3);
+        uint32_t s1 = ROTR(w[i - 2], 17) ^ ROTR(w[i - 2], 19) ^ (w[i - 2] >> 10);
+        w[i] = w[i - 16] + s0 + w[i - 7] + s1;
+    }
+
+    uint32_t a = state[0], b = state[1], c = state[2], d = state[3];
+    uint32_t e = state[4], f = state[5], g = state[6], h = state[7];
+    for (int i = 0; i < 64; i++) {
+        uint32_t s1 = ROTR(e, 6) ^ ROTR(e, 11) ^ ROTR(e, 25);
+        uint32_t ch = (e & f) ^ (~e & g);
+        uint32_t t1 = h + s1 + ch + k[i] + w[i];
+        uint32_t s0 = ROTR(a, 2) ^ ROTR(a, 13) ^ ROTR(a, 22);
+        uint32_t maj = (a & b) ^ (a & c) ^ (b & c);
+        uint32_t t2 = s0 + maj;
+        h = g;
+        g = f;
+        f = e;
+        e = d + t1;
+        d = c;
+        c = b;
+        b = a;
+        a = t1 + t2;
+    }
+    state[0] += a;
+    state[1] += b;
+    state[2] += c;
+    state[3] += d;
+    state[4] += e;
+    state[5] += f;
+    state[6] += g;
+    state[7] += h;
+}
+
+MINING_HD static inline void sha256_init(sha256_ctx* ctx) {
+    ctx->state[0] = 0x6a09e667;
+    ctx->state[1] = 0xbb67ae85;
+    ctx->state[2] = 0x3c6ef372;
+    ctx->state[3] = 0xa54ff53a;
+    ctx->state[4] = 0x510e527f;
+    ctx->state[5] = 0x9b05688c;
+    ctx->state[6] = 0x1f83d9ab;
+    ctx->state[7] = 0x5be0cd19;
+    ctx->length = 0;
+    ctx->buffered = 0;
+}
+
+MINING_HD static inline void sha256_update(sha256_ctx* ctx, const uint8_t* data, size_t len) {
+    for (size_t i = 0; i < len; i++) {
+        ctx->buffer[ctx->buffered++] = data[i];
+        if (ctx->buffered == 64) {
+            sha256_transform(ctx->state, ctx->buffer);
+            ctx->buffered = 0;
+        }
+    }
+    ctx->length += len;
+}
+
+MINING_HD static inline void sha256_final(sha256_ctx* ctx, uint8_t hash[32]) {
+    uint64_t bits = ctx->length * 8;
+    uint8_t pad = 0x80;
+    sha256_update(ctx, &pad, 1);
+    pad = 0;
+    while (ctx->buffered != 56) {
+        sha256_update(ctx, &pad, 1);
+    }
+    uint8_t length[8];
+    for (int i = 0; i < 8; i++) {
+        length[i] = (uint8_t)(bits >> (56 - i * 8));
+    }
+    sha256_update(ctx, length, 8);
+    for (int i = 0; i < 8; i++) {
+        hash[i * 4] = (uint8_t)(ctx->state[i] >> 24);
+        hash[i * 4 + 1] = (uint8_t)(ctx->state[i] >> 16);
+        hash[i * 4 + 2] = (uint8_t)(ctx->state[i] >> 8);
+        hash[i * 4 + 3] = (uint8_t)ctx->state[i];
+    }
+}
+
+/* Count the zero hex digits at the start of the hash */
+MINING_HD static inline int mining_leading_zeros(const uint8_t hash[32]) {
+    int count = 0;
+    for (int i = 0; i < 32; i++) {
+        if (hash[i] == 0) {
+            count += 2;
+            continue;
+        }
+        if (hash[i] < 16) {
+            count += 1;
+        }
+        break;
+    }
+    return count;
+}
+
+/* Hash the block data followed by the decimal nonce, return 1 if the hash has the difficulty */
+MINING_HD static inline int mining_check_nonce(const char* data, int len, int nonce, int difficulty,
+                                               uint8_t hash[32]) {
+    uint8_t digits[10];
+    int count = 0;
+    do {
+        digits[9 - count++] = (uint8_t)('0' + nonce % 10);
+        nonce /= 10;
+    } while (nonce > 0);
+
+    sha256_ctx ctx;
+    sha256_init(&ctx);
+    sha256_update(&ctx, (const uint8_t*)data, (size_t)len);
+    sha256_update(&ctx, digits + 10 - count, (size_t)count);
+    sha256_final(&ctx, hash);
+    return mining_leading_zeros(hash) >= difficulty;
+}
+
+#endif
@@ -0,0 +1,63 @@
+/*
+ * GPU mining kernel
+ * Every launch tries one batch of consecutive nonces, one nonce per thread, and keeps the lowest
+ * winning nonce with atomicMin. The batches are launched in order until a nonce is found,
+ * so the result is the lowest winning nonce, like the CPU reference.
+ */
+#include <climits>
+#include <cuda_runtime.h>
+
+#include "mining.h"
+#include "sha256.h"
+
+#define THREADS_PER_BLOCK 256
+#define BLOCKS_PER_BATCH 4096
+
+__global__ void search_nonces(const char* data, int len, int difficulty, int start, int max_nonce,
+                              int* found) {
+    long long nonce = (long long)start + (long long)blockIdx.x * blockDim.x + threadIdx.x;
+    if (nonce >= max_nonce) {
+        return;
+    }
+    uint8_t hash[32];
+    if (mining_check_nonce(data, len, (int)nonce, difficulty, hash)) {
+        atomicMin(found, (int)nonce);
+    }
+}
+
+extern "C" void mine_block(const char* block_data, int data_len, int difficulty,
+                           unsigned char* final_hash, int* final_nonce, int start_nonce,
+                           int max_nonce) {
+    *final_nonce = MINING_NO_NONCE;
+    char* device_data = NULL;
+    int* device_found = NULL;
+    int found = INT_MAX;
+    if (cudaMalloc(&device_data, data_len > 0 ? data_len : 1) != cudaSuccess ||
+        cudaMalloc(&device_found, sizeof(int)) != cudaSuccess ||
+        cudaMemcpy(device_data, block_data, data_len, cudaMemcpyHostToDevice) != cudaSuccess ||
+        cudaMemcpy(device_found, &found, sizeof(int), cudaMemcpyHostToDevice) != cudaSuccess) {
+        *final_nonce = MINING_GPU_ERROR;
+        cudaFree(device_data);
+        cudaFree(device_found);
+        return;
+    }
+
+    const long long batch = (long long)THREADS_PER_BLOCK * BLOCKS_PER_BATCH;
+    for (long long start = start_nonce; start < max_nonce && found == INT_MAX; start += batch) {
+        search_nonces<<<BLOCKS_PER_BATCH, THREADS_PER_BLOCK>>>(device_data, data_len, difficulty,
+                                                               (int)start, max_nonce, device_found);
+        if (cudaMemcpy(&found, device_found, sizeof(int), cudaMemcpyDeviceToHost) != cudaSuccess) {
+            *final_nonce = MINING_GPU_ERROR;
+            found = INT_MAX;
+            break;
+        }
+    }
+    cudaFree(device_data);
+    cudaFree(device_found);
+
+    // the hash of the winning nonce is computed again on the host
+    if (found != INT_MAX) {
+        mining_check_nonce(block_data, data_len, found, difficulty, final_hash);
+        *final_nonce = found;
+    }
+}
@@ -0,0 +1,18 @@
+/*
+ * CPU reference of the mining kernel
+ * It tries the nonces in order, so it finds the same nonce as the GPU kernel.
+ */
+#include "mining.h"
+#include "sha256.h"
+
+void mine_block_cpu(const char* block_data, int data_len, int difficulty,
+                    unsigned char* final_hash, int* final_nonce, int start_nonce,
+                    int max_nonce) {
+    *final_nonce = MINING_NO_NONCE;
+    for (int nonce = start_nonce; nonce < max_nonce; nonce++) {
+        if (mining_check_nonce(block_data, data_len, nonce, difficulty, final_hash)) {
+            *final_nonce = nonce;
+            return;
+        }
+    }
+}
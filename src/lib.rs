@@ -5,40 +5,103 @@ pub mod models;
 pub mod node {
     tonic::include_proto!("node");
 }
-use crate::models::{blockchain::mine_new_block, network::Network, node::Node};
-use node::node_message_client::NodeMessageClient;
-use node::{
-    node_message_server::NodeMessageServer, Block, JoinNetworkRequest, NodeInfo,
-    UpdateBlockchainRequest,
+use crate::models::{
+    block_tree::ReorgEvent,
+    blockchain::{block_template, chain_work, next_timestamp},
+    broadcast::ChainSnapshot,
+    light_client::LightClient,
+    miner::MiningStats,
+    network::Network,
+    node::{Node, NodeConfig},
+    storage::TRANSACTIONS_WRITE_INTERVAL,
 };
+use node::node_message_client::NodeMessageClient;
+use node::{node_message_server::NodeMessageServer, JoinNetworkRequest};
+use std::net::SocketAddr;
 use std::sync::Arc;
-use tokio::{sync::mpsc, task::JoinSet};
+use tokio::{
+    sync::{broadcast, mpsc},
+    task::JoinSet,
+};
 use tonic::{transport::Server, Request};
 
 /// Start the node server
 pub async fn start(port: u16, peer_port: Option<u16>) {
-    let port: u32 = port as u32;
-    let (tx, rx) = mpsc::channel::<bool>(1);
-    let node = Arc::new(Node::new(port));
+    start_with_config(NodeConfig::new(port, peer_port)).await
+}
+
+/// Start a light client, which keeps the block headers of the peer node in sync and checks the watched transactions against them
+pub async fn start_light_client(config: NodeConfig) {
+    let peer = config
+        .peer_url()
+        .expect("A light client needs a peer node to follow");
+    let mut light_client = LightClient::connect(peer, config.params)
+        .await
+        .expect("Failed to connect to peer node");
+    loop {
+        match light_client.sync_headers().await {
+            Ok(true) => {
+                println!(
+                    "[INFO] Synced block headers, height: {}",
+                    light_client.headers.len()
+                );
+                check_transactions(&mut light_client, &config.watch_transactions).await;
+            }
+            Ok(false) => {}
+            Err(e) => println!("[Warning] Failed to sync block headers: {}", e.message()),
+        }
+        tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
+    }
+}
+
+/// Check the watched transactions with merkle proofs from the peer node against the synced headers, and log their confirmations
+/// The transactions are watched by hash, since the id of a transaction is chosen by its sender and is not signed
+async fn check_transactions(light_client: &mut LightClient, hashes: &[String]) {
+    for hash in hashes {
+        match light_client.verify_transaction(hash).await {
+            Ok(Some(confirmations)) => println!(
+                "[INFO] Transaction {} confirmed, {} confirmations",
+                hash, confirmations
+            ),
+            Ok(None) => println!(
+                "[Warning] Transaction {} has a merkle proof that does not match the headers or is for another transaction",
+                hash
+            ),
+            Err(e) => println!(
+                "[INFO] Transaction {} is not confirmed: {}",
+                hash,
+                e.message()
+            ),
+        }
+    }
+}
+
+/// Start the node server with the given configuration
+pub async fn start_with_config(config: NodeConfig) {
+    if config.light_client {
+        return start_light_client(config).await;
+    }
+    let (tx, mut rx) = mpsc::channel::<bool>(1);
+    let node = Arc::new(Node::new(&config));
     let network = Network {
         node: node.clone(),
         tx,
     };
-    let node_info = NodeInfo {
-        id: node.id.to_string(),
-        ip: node.ip.clone(),
-        port: node.port,
-    };
+    let node_info = node.info();
+    let network_magic = config.params.network_magic;
+    let genesis_hash = config.params.genesis_hash().to_string();
 
     // if the node is not the master node, then should introduce itself to every node in the network
-    if let Some(peer) = peer_port {
+    if let Some(peer) = config.peer_url() {
         // connect to the peer node
-        let mut client = NodeMessageClient::connect(format!("http://127.0.0.1:{}", peer))
+        let mut client = NodeMessageClient::connect(peer)
             .await
             .expect("Failed to connect to peer node");
         let res = client
             .join_network(Request::new(JoinNetworkRequest {
                 node: Some(node_info.clone()),
+                network_magic,
+                genesis_hash: genesis_hash.clone(),
             }))
             .await
             .expect("Failed to join network on peer node");
@@ -46,30 +109,54 @@ pub async fn start(port: u16, peer_port: Option<u16>) {
         // get the peer list and the blockchain from the peer node
         let res = res.into_inner();
         let peer_list = res.nodes;
+        let peer_id = res.node.map(|peer| peer.id);
         node.peers.lock().await.extend(peer_list.clone());
-        node.blockchain.lock().await.chain = res.chain;
-        node.blockchain.lock().await.transactions = res.transactions;
+        let mut blockchain = node.blockchain.lock().await;
+        if res.chain.first() != Some(&config.params.genesis) {
+            panic!("Peer node sent a blockchain that starts from another genesis block");
+        }
+        // a restarted node keeps its stored chain unless the peer has more work
+        if chain_work(&res.chain) > blockchain.total_work() {
+            match blockchain.replace_chain(res.chain) {
+                Ok(true) => {}
+                Ok(false) => panic!("Peer node sent a blockchain that overspends account balances"),
+                Err(e) => panic!("Failed to store the blockchain of the peer node: {}", e),
+            }
+        }
+        for transaction in res.transactions {
+            let _ = blockchain.add_transaction(transaction);
+        }
+        drop(blockchain);
 
         // broadcast the new node to the rest of the network
         let mut broadcast = JoinSet::new();
 
-        peer_list.into_iter().for_each(|node| {
-            if node.port == port || node.port == peer as u32 {
+        peer_list.into_iter().for_each(|peer| {
+            // the peer node already knows this node
+            if peer.id == node_info.id || Some(&peer.id) == peer_id.as_ref() {
                 return;
             }
-            let node_info = node_info.clone();
+            let node = node.clone();
+            let request = Request::new(JoinNetworkRequest {
+                node: Some(node_info.clone()),
+                network_magic,
+                genesis_hash: genesis_hash.clone(),
+            });
             broadcast.spawn(async move {
-                println!("[INFO] Broadcasting to node: {:?}", node.port);
-                let mut client =
-                    NodeMessageClient::connect(format!("http://{}:{}", node.ip, node.port))
-                        .await
-                        .unwrap();
-                client
-                    .join_network(Request::new(JoinNetworkRequest {
-                        node: Some(node_info.clone()),
-                    }))
-                    .await
-                    .unwrap();
+                println!("[INFO] Broadcasting to node: {}", peer.url());
+                let result = node
+                    .peer_manager
+                    .call(&peer, |mut client| async move {
+                        client.join_network(request).await
+                    })
+                    .await;
+                if let Err(error) = result {
+                    println!(
+                        "[Warning] Failed to join network on node: {}, {}",
+                        peer.url(),
+                        error.message()
+                    );
+                }
             });
         });
         while let Some(_) = broadcast.join_next().await {}
@@ -79,86 +166,131 @@ pub async fn start(port: u16, peer_port: Option<u16>) {
     }
 
     // start a thread to handle incoming transactions, if any transaction is received, compute the hash and add it to the blockchain
-    let addr = format!("127.0.0.1:{}", port).parse().unwrap();
+    let addr = SocketAddr::new(config.listen_ip, config.port);
 
-    tokio::spawn(handle_transactions(node, port, rx));
+    tokio::spawn(log_reorgs(node.blockchain.lock().await.subscribe()));
+    tokio::spawn(report_mining(node.miner.stats.clone()));
+    if config.data_dir.is_some() {
+        tokio::spawn(store_transactions(node.clone()));
+    }
+    if config.params.mine_on_demand {
+        // blocks are only mined by GenerateBlocks requests, so there is no mining to stop
+        tokio::spawn(async move { while rx.recv().await.is_some() {} });
+    } else {
+        tokio::spawn(handle_transactions(node, rx));
+    }
 
-    println!("[INFO] Node server listening on {}", addr);
+    println!(
+        "[INFO] Node server listening on {}, advertised as {}",
+        addr,
+        node_info.url()
+    );
     Server::builder()
         .add_service(NodeMessageServer::new(network))
         .serve(addr)
         .await
         .unwrap();
 }
+/// Log the reorganizations of the blockchain, where blocks of the active chain are replaced by the blocks of another branch
+async fn log_reorgs(mut events: broadcast::Receiver<ReorgEvent>) {
+    loop {
+        match events.recv().await {
+            Ok(event) if !event.disconnected.is_empty() => println!(
+                "[INFO] Blockchain reorganized at height {}, {} blocks disconnected, {} blocks connected",
+                event.fork_height,
+                event.disconnected.len(),
+                event.connected.len()
+            ),
+            Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => {}
+            Err(broadcast::error::RecvError::Closed) => return,
+        }
+    }
+}
+
+/// Sample the hash count of the miner every second, and log the hashrate every minute while the node mines
+async fn report_mining(stats: Arc<MiningStats>) {
+    let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(1));
+    let mut reported_hashes = 0;
+    for tick in 1u64.. {
+        interval.tick().await;
+        stats.record_sample();
+        if tick % 60 != 0 || stats.hashes() == reported_hashes {
+            continue;
+        }
+        reported_hashes = stats.hashes();
+        let info = stats.info();
+        println!(
+            "[INFO] Mining {:.0} H/s (1m), {:.0} H/s (5m), {:.0} H/s (15m), {} blocks mined, {} interrupted, last block {}s ago",
+            info.hashrate1m,
+            info.hashrate5m,
+            info.hashrate15m,
+            info.blocks_mined,
+            info.interrupted,
+            info.seconds_since_last_block
+        );
+    }
+}
+
+/// Write the transaction pool to the block store when it changed, without holding the blockchain lock while writing
+async fn store_transactions(node: Arc<Node>) {
+    let mut interval = tokio::time::interval(TRANSACTIONS_WRITE_INTERVAL);
+    loop {
+        interval.tick().await;
+        let Some(write) = node.blockchain.lock().await.transactions_write() else {
+            continue;
+        };
+        if let Ok(Err(e)) = tokio::task::spawn_blocking(move || write.write()).await {
+            println!(
+                "[Warning] Failed to write the transaction pool to disk: {}",
+                e
+            );
+        }
+    }
+}
+
 /// Handle incoming transactions
-pub async fn handle_transactions(node: Arc<Node>, port: u32, mut rx: mpsc::Receiver<bool>) {
+pub async fn handle_transactions(node: Arc<Node>, mut rx: mpsc::Receiver<bool>) {
     loop {
-        let blockchain = node.blockchain.lock().await;
-        let difficulty = blockchain.difficulty;
-        let last_block = blockchain
-            .chain
-            .last()
-            .unwrap_or(&Block {
-                id: -1,
-                timestamp: 0,
-                nonce: 0,
-                prev_hash: "".to_string(),
-                hash: "".to_string(),
-                transactions: vec![],
-                difficulty: difficulty,
-            })
-            .clone();
+        let mut blockchain = node.blockchain.lock().await;
+        let expired = blockchain.expire_transactions();
+        if expired > 0 {
+            println!(
+                "[INFO] {} transactions expired from the transaction pool",
+                expired
+            );
+        }
+        let difficulty = blockchain.next_difficulty();
+        let timestamp = next_timestamp(&blockchain.chain);
+        // the chain always starts with the genesis block
+        let last_block = blockchain.chain.last().unwrap().clone();
 
-        let transactions = blockchain.transactions.clone();
-        drop(blockchain);
         // if there are transactions in the transaction pool, then mine a new block
-        if transactions.is_empty() {
+        if blockchain.mempool.is_empty() {
+            drop(blockchain);
             tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
             continue;
         }
-        match mine_new_block(&last_block, transactions.clone(), difficulty, &mut rx).await {
+        let transactions = blockchain.block_transactions(&node.miner_address);
+        drop(blockchain);
+        let block = block_template(&last_block, transactions, difficulty, timestamp);
+        match node.miner.mine_block(block, &mut rx).await {
             Ok(block) => {
-                // broadcast the new block to the rest of the network
+                // broadcast the new block to the rest of the network, without waiting for the peers
                 let mut blockchain = node.blockchain.lock().await;
-                blockchain.chain.push(block.clone());
-
-                for node in node.peers.lock().await.iter() {
-                    if node.port == port {
+                match blockchain.push_block(block.clone()) {
+                    Ok(true) => {}
+                    Ok(false) => {
+                        println!("[Warning] Mined block no longer fits the blockchain");
                         continue;
                     }
-                    let mut start_idx = blockchain.chain.len() - 1;
-                    let mut client =
-                        NodeMessageClient::connect(format!("http://127.0.0.1:{}", node.port))
-                            .await
-                            .unwrap();
-                    loop {
-                        match client
-                            .update_blockchain(Request::new(UpdateBlockchainRequest {
-                                blocks: blockchain.chain[start_idx..].to_vec(),
-                            }))
-                            .await
-                        {
-                            Ok(res) => {
-                                let response = res.into_inner();
-                                if response.success {
-                                    break;
-                                } else {
-                                    start_idx = response.chain_length as usize;
-                                }
-                            }
-                            Err(error) => {
-                                eprintln!(
-                                    "[Warning] Failed to update blockchain on node: {}, {:?}",
-                                    node.port, error
-                                );
-                                break;
-                            }
-                        }
+                    Err(e) => {
+                        println!("[Warning] Failed to store the mined block: {}", e);
+                        continue;
                     }
                 }
-                transactions.iter().for_each(|transaction| {
-                    blockchain.transactions.retain(|x| x != transaction);
-                });
+                let snapshot = ChainSnapshot::new(&blockchain);
+                drop(blockchain);
+                node.broadcast_chain(snapshot).await;
             }
             Err(error) => {
                 println!("[Warning] Failed to mine new block: {:?}", error);
@@ -1,10 +1,19 @@
 /// This is the main entry point for the blockchain application
+use blockchain::models::chain_params::ChainParams;
 use blockchain::models::client::Client;
-use blockchain::start;
+use blockchain::models::node::NodeConfig;
+use blockchain::start_with_config;
 use clap::Parser;
-use igd::aio::search_gateway;
+use igd::aio::{search_gateway, Gateway};
+use igd::AddPortError;
 use local_ip_address::local_ip;
-use std::net::{Ipv4Addr, SocketAddrV4};
+use std::net::{IpAddr, SocketAddrV4};
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// The lease of the port forwarded on the gateway, in seconds, the forwarding is renewed halfway through every lease
+const PORT_MAPPING_DURATION: u32 = 60;
+
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
 struct Args {
@@ -15,21 +24,58 @@ struct Args {
     #[arg(short, long)]
     peer_port: Option<u16>,
 
+    /// The IP address of the peer node
+    #[arg(long, default_value = "127.0.0.1")]
+    peer_ip: String,
+
+    /// The IP address to listen on
+    #[arg(long, default_value = "127.0.0.1")]
+    listen_ip: IpAddr,
+
+    /// The IP address advertised to the other nodes, the listen address by default
+    #[arg(short, long)]
+    external_ip: Option<String>,
+
+    /// Discover the address to advertise from the local network, and forward the port on the gateway
+    #[arg(long, action)]
+    discover_ip: bool,
+
     /// is client
     #[arg(short, long, action)]
     client: bool,
+
+    /// The address (public key) that receives the mining rewards
+    #[arg(short, long)]
+    miner_address: Option<String>,
+
+    /// Run as a light client that only follows the block headers of the peer node
+    #[arg(short, long, action)]
+    light: bool,
+
+    /// The hash of a transaction the light client checks with a merkle proof, can be given several times
+    #[arg(short, long = "watch")]
+    watch: Vec<String>,
+
+    /// The directory where the blockchain is stored across restarts
+    #[arg(short, long)]
+    data_dir: Option<PathBuf>,
+
+    /// A chain archive to import when the node starts
+    #[arg(short, long)]
+    import_chain: Option<PathBuf>,
+
+    /// The network to join: mainnet, testnet or regtest
+    #[arg(short, long, default_value = "mainnet")]
+    network: String,
+
+    /// The number of threads used to mine blocks, one per CPU by default
+    #[arg(short = 't', long)]
+    mining_threads: Option<usize>,
 }
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let args = Args::parse();
-    let gateway = match search_gateway(Default::default()).await {
-        Ok(gateway) => gateway,
-        Err(e) => {
-            println!("[ERROR] Failed to find gateway: {}", e);
-            return Ok(());
-        }
-    };
 
     if args.client {
         // start the client
@@ -38,46 +84,123 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         client.start().await;
         Ok(())
     } else {
-        let external_port = 9487;
-        let duration = 60;
-        let local_ip = local_ip()
-            .expect("Failed to get local IP address")
-            .to_string();
-        let local_ip = local_ip
-            .parse::<Ipv4Addr>()
-            .expect("failed to parse IP address");
-        let local_ip = SocketAddrV4::new(local_ip, args.port);
-        print!("local_ip: {:?}\n", local_ip);
-
-        match gateway
-            .add_port(
-                igd::PortMappingProtocol::TCP,
-                external_port,
-                local_ip,
-                duration,
-                "YoutaCoin blockchain",
-            )
-            .await
-        {
-            Ok(_) => {
-                println!("[INFO] Port {} forwarded to {}", args.port, local_ip);
-            }
-            Err(e) => {
-                println!("[ERROR] Failed to forward port: {}", e);
-            }
+        let mut config = NodeConfig::new(args.port, args.peer_port);
+        config.peer_ip = args.peer_ip;
+        config.listen_ip = args.listen_ip;
+        config.external_ip = args.external_ip;
+        config.miner_address = args.miner_address;
+        config.light_client = args.light;
+        config.watch_transactions = args.watch;
+        config.data_dir = args.data_dir;
+        config.import_chain = args.import_chain;
+        if let Some(threads) = args.mining_threads {
+            config.mining_threads = threads;
         }
-        start(args.port, args.peer_port).await;
-        match gateway
-            .remove_port(igd::PortMappingProtocol::TCP, external_port)
-            .await
-        {
-            Ok(_) => {
-                println!("[INFO] Port {} removed", args.port);
+        config.params = match ChainParams::from_name(&args.network) {
+            Some(params) => params,
+            None => {
+                println!("[ERROR] Unknown network: {}", args.network);
+                return Ok(());
             }
-            Err(e) => {
-                println!("[ERROR] Failed to remove port: {}", e);
+        };
+        let gateway = if args.discover_ip {
+            discover_ip(&mut config).await
+        } else {
+            None
+        };
+        start_with_config(config).await;
+        if let Some(gateway) = gateway {
+            match gateway
+                .remove_port(igd::PortMappingProtocol::TCP, args.port)
+                .await
+            {
+                Ok(_) => {
+                    println!("[INFO] Port {} removed", args.port);
+                }
+                Err(e) => {
+                    println!("[ERROR] Failed to remove port: {}", e);
+                }
             }
         }
         Ok(())
     }
 }
+
+/// Listen on the local network address, and advertise the external address of the gateway once the port is forwarded to the node
+/// The local network address is advertised if the port cannot be forwarded, and an external address given by the user is kept
+/// The forwarding is renewed in the background, so the advertised address stays reachable while the node runs
+/// Return the gateway that forwards the port, to remove the forwarding when the node stops
+async fn discover_ip(config: &mut NodeConfig) -> Option<Gateway> {
+    let local_ip = match local_ip() {
+        Ok(IpAddr::V4(ip)) => ip,
+        Ok(ip) => {
+            println!("[ERROR] Local IP address is not an IPv4 address: {}", ip);
+            return None;
+        }
+        Err(e) => {
+            println!("[ERROR] Failed to get local IP address: {}", e);
+            return None;
+        }
+    };
+    println!("[INFO] Local IP address: {}", local_ip);
+    if config.listen_ip.is_loopback() {
+        config.listen_ip = IpAddr::V4(local_ip);
+    }
+    let discover_external_ip = config.external_ip.is_none();
+    if discover_external_ip {
+        config.external_ip = Some(local_ip.to_string());
+    }
+
+    let gateway = match search_gateway(Default::default()).await {
+        Ok(gateway) => gateway,
+        Err(e) => {
+            println!("[ERROR] Failed to find gateway: {}", e);
+            return None;
+        }
+    };
+    // the same port is forwarded, as the other nodes dial the port the node listens on
+    let local_addr = SocketAddrV4::new(local_ip, config.port);
+    match forward_port(&gateway, local_addr).await {
+        Ok(_) => {
+            println!("[INFO] Port {} forwarded to {}", config.port, local_addr);
+        }
+        Err(e) => {
+            println!("[ERROR] Failed to forward port: {}", e);
+            return None;
+        }
+    }
+    tokio::spawn(renew_port(gateway.clone(), local_addr));
+    if discover_external_ip {
+        match gateway.get_external_ip().await {
+            Ok(ip) => config.external_ip = Some(ip.to_string()),
+            Err(e) => println!("[ERROR] Failed to get external IP address: {}", e),
+        }
+    }
+    Some(gateway)
+}
+
+/// Forward the port of the local address on the gateway for one lease
+async fn forward_port(gateway: &Gateway, local_addr: SocketAddrV4) -> Result<(), AddPortError> {
+    gateway
+        .add_port(
+            igd::PortMappingProtocol::TCP,
+            local_addr.port(),
+            local_addr,
+            PORT_MAPPING_DURATION,
+            "YoutaCoin blockchain",
+        )
+        .await
+}
+
+/// Forward the port again halfway through every lease, so the forwarding does not expire while the node runs
+async fn renew_port(gateway: Gateway, local_addr: SocketAddrV4) {
+    let mut renewal = tokio::time::interval(Duration::from_secs(PORT_MAPPING_DURATION as u64 / 2));
+    // the first tick completes right away, and the port was just forwarded
+    renewal.tick().await;
+    loop {
+        renewal.tick().await;
+        if let Err(e) = forward_port(&gateway, local_addr).await {
+            println!("[Warning] Failed to renew the forwarded port: {}", e);
+        }
+    }
+}
@@ -1,5 +1,18 @@
+pub mod archive;
+pub mod block_tree;
 pub mod blockchain;
+pub mod broadcast;
+pub mod chain_params;
 pub mod client;
 pub mod cudalib;
+pub mod ledger;
+pub mod light_client;
+pub mod mempool;
+pub mod merkle;
+pub mod miner;
 pub mod network;
 pub mod node;
+pub mod orphan;
+pub mod peers;
+pub mod storage;
+pub mod template;
@@ -0,0 +1,98 @@
+//! ## Archive module
+//! A chain archive is a file holding a whole blockchain, used to seed new nodes with a known chain
+//! The file starts with a header made of the magic bytes, the format version and the hash of the genesis block
+//! The header is followed by the blocks of the chain, each one as a length-prefixed protobuf record
+//! An imported chain is fully validated before it is used, and the first invalid block is reported on failure
+use crate::models::blockchain::Blockchain;
+use crate::models::storage::{decode_record, encode_record, invalid_data, record_length};
+use crate::node::Block;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// The magic bytes at the start of every chain archive
+pub const ARCHIVE_MAGIC: &[u8; 4] = b"BOBA";
+/// The version of the chain archive format written by this node
+pub const ARCHIVE_VERSION: u32 = 1;
+
+/// The reason an archive could not be imported
+#[derive(Debug)]
+pub enum ImportError {
+    /// The file could not be read, or is not a valid chain archive
+    Io(io::Error),
+    /// The block at the given height is invalid
+    InvalidBlock(usize),
+}
+
+impl fmt::Display for ImportError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ImportError::Io(e) => write!(f, "{}", e),
+            ImportError::InvalidBlock(height) => write!(f, "block {} is invalid", height),
+        }
+    }
+}
+
+impl From<io::Error> for ImportError {
+    fn from(e: io::Error) -> ImportError {
+        ImportError::Io(e)
+    }
+}
+
+/// Write the chain to an archive file
+pub fn export_chain(path: impl AsRef<Path>, chain: &[Block]) -> io::Result<()> {
+    let genesis_hash = chain.first().map(|b| b.hash.as_str()).unwrap_or("");
+    let mut buf = ARCHIVE_MAGIC.to_vec();
+    buf.extend(ARCHIVE_VERSION.to_le_bytes());
+    buf.extend((genesis_hash.len() as u32).to_le_bytes());
+    buf.extend(genesis_hash.as_bytes());
+    for block in chain {
+        buf.extend(encode_record(block));
+    }
+    fs::write(path, buf)
+}
+
+/// Read the chain from an archive file, the blocks are not validated
+pub fn read_chain(path: impl AsRef<Path>) -> io::Result<Vec<Block>> {
+    let buf = fs::read(path)?;
+    if buf.len() < 8 || &buf[..4] != ARCHIVE_MAGIC {
+        return Err(invalid_data("Not a chain archive"));
+    }
+    let version = u32::from_le_bytes(buf[4..8].try_into().unwrap());
+    if version != ARCHIVE_VERSION {
+        return Err(invalid_data(&format!(
+            "Unsupported chain archive version {}",
+            version
+        )));
+    }
+    let length = record_length(&buf[8..])?;
+    let genesis_hash = String::from_utf8(buf[12..8 + length].to_vec())
+        .map_err(|_| invalid_data("Invalid genesis hash"))?;
+
+    let mut chain = Vec::new();
+    let mut rest = &buf[8 + length..];
+    while !rest.is_empty() {
+        let length = record_length(rest)?;
+        chain.push(decode_record::<Block>(&rest[..length])?);
+        rest = &rest[length..];
+    }
+    if chain.first().map(|b| b.hash.as_str()).unwrap_or("") != genesis_hash {
+        return Err(invalid_data(
+            "The genesis hash does not match the first block",
+        ));
+    }
+    Ok(chain)
+}
+
+/// Import the chain of an archive file into the blockchain
+/// Return the height of the imported chain
+pub fn import_chain(
+    path: impl AsRef<Path>,
+    blockchain: &mut Blockchain,
+) -> Result<usize, ImportError> {
+    let chain = read_chain(path)?;
+    let height = chain.len();
+    blockchain.import_chain(chain)?;
+    Ok(height)
+}
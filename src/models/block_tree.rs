@@ -0,0 +1,88 @@
+//! ## Block tree module
+//! The block tree keeps the blocks of competing side branches indexed by their hash, the blocks of the active chain are only kept in the chain
+//! A side branch is kept so that the node can switch to it without downloading it again, once it has more accumulated work than the active chain
+//! When the active chain changes, a reorg event reports the blocks that were disconnected and connected, and the height where the chains fork
+use crate::node::Block;
+use std::collections::{HashMap, HashSet};
+
+/// How many blocks below the tip of the active chain the blocks of side branches are kept
+pub const MAX_SIDE_BRANCH_DEPTH: i32 = 100;
+
+/// A change of the active chain
+#[derive(Clone, Debug, PartialEq)]
+pub struct ReorgEvent {
+    /// The height of the first block that differs between the old and the new active chain
+    pub fork_height: usize,
+    /// The blocks removed from the active chain, in chain order
+    pub disconnected: Vec<Block>,
+    /// The blocks added to the active chain, in chain order
+    pub connected: Vec<Block>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct BlockTree {
+    blocks: HashMap<String, Block>,
+}
+
+impl BlockTree {
+    /// Add a block to the tree
+    pub fn insert(&mut self, block: Block) {
+        self.blocks.entry(block.hash.clone()).or_insert(block);
+    }
+
+    /// Remove the block with the given hash from the tree
+    pub fn remove(&mut self, hash: &str) -> Option<Block> {
+        self.blocks.remove(hash)
+    }
+
+    /// Get the block with the given hash
+    pub fn get(&self, hash: &str) -> Option<&Block> {
+        self.blocks.get(hash)
+    }
+
+    /// Check if the block with the given hash is in the tree
+    pub fn contains(&self, hash: &str) -> bool {
+        self.blocks.contains_key(hash)
+    }
+
+    /// Get the number of blocks in the tree
+    pub fn len(&self) -> usize {
+        self.blocks.len()
+    }
+
+    /// Check if the tree is empty
+    pub fn is_empty(&self) -> bool {
+        self.blocks.is_empty()
+    }
+
+    /// Get the blocks of the tree that lead to the block with the given hash, from the first block whose parent is not in the tree
+    /// Return an empty branch if the block is not in the tree
+    pub fn branch(&self, hash: &str) -> Vec<Block> {
+        let mut branch = Vec::new();
+        let mut hash = hash;
+        while let Some(block) = self.blocks.get(hash) {
+            branch.push(block.clone());
+            hash = &block.prev_hash;
+        }
+        branch.reverse();
+        branch
+    }
+
+    /// Get the blocks that no other block of the tree follows, which are the tips of the side branches
+    pub fn tips(&self) -> Vec<&Block> {
+        let parents: HashSet<&str> = self
+            .blocks
+            .values()
+            .map(|block| block.prev_hash.as_str())
+            .collect();
+        self.blocks
+            .values()
+            .filter(|block| !parents.contains(block.hash.as_str()))
+            .collect()
+    }
+
+    /// Only keep the blocks for which the predicate returns true
+    pub fn retain(&mut self, mut keep: impl FnMut(&Block) -> bool) {
+        self.blocks.retain(|_, block| keep(block));
+    }
+}
@@ -4,84 +4,564 @@
 //! The blockchain is used to store the transactions and blocks, and to validate the blocks
 //! The blocks are mined by the nodes, and the transactions are added to the blocks
 //! The blocks are mined by solving a cryptographic puzzle, and the first node to solve the puzzle gets to add the block to the blockchain
-use crate::node::{Block, Transaction};
+use crate::models::archive::ImportError;
+use crate::models::block_tree::{BlockTree, ReorgEvent, MAX_SIDE_BRANCH_DEPTH};
+use crate::models::chain_params::ChainParams;
+use crate::models::ledger::Ledger;
+use crate::models::mempool::{Mempool, RejectReason};
+use crate::models::merkle::merkle_root;
+use crate::models::miner::{default_backend, default_threads, Miner};
+use crate::models::storage::{BlockStore, TransactionsWrite};
+use crate::models::template::select_transactions;
+use crate::node::{Block, BlockHeader, Transaction};
 use openssl::{hash::MessageDigest, pkey::PKey, rsa::Rsa, sign::Verifier};
+use prost::Message;
+use std::io;
 use std::time::SystemTime;
+use tokio::sync::broadcast;
 use tokio::sync::mpsc::Receiver;
 use tonic::Status;
+use uuid::Uuid;
+
+/// The number of previous blocks used to compute the median time past
+pub const MEDIAN_TIME_SPAN: usize = 11;
+/// How far in the future, in seconds, the timestamp of a block can be
+pub const MAX_FUTURE_BLOCK_TIME: u32 = 2 * 60 * 60;
+/// The number of reorg events a subscriber can fall behind before it misses events
+pub const REORG_EVENT_CAPACITY: usize = 64;
 pub struct Blockchain {
-    pub transactions: Vec<Transaction>,
+    /// The transactions waiting to be included in a block
+    pub mempool: Mempool,
+    /// The active chain, from the genesis block to the tip with the most accumulated work
     pub chain: Vec<Block>,
-    pub difficulty: i32,
+    /// The blocks of the side branches, which are not on the active chain
+    pub tree: BlockTree,
+    pub params: ChainParams,
+    pub ledger: Ledger,
+    allocations: Vec<(String, i64)>,
+    storage: Option<BlockStore>,
+    /// Whether the transaction pool changed since it was last written to the block store
+    transactions_changed: bool,
+    events: broadcast::Sender<ReorgEvent>,
+}
+
+/// The difficulty retarget schedule
+/// Every `retarget_interval` blocks, the difficulty is adjusted by one leading zero so that blocks are mined about every `target_block_time` seconds
+#[derive(Clone, Debug)]
+pub struct DifficultySchedule {
+    pub initial_difficulty: i32,
+    pub retarget_interval: i32,
+    pub target_block_time: u32,
+}
+
+impl Default for DifficultySchedule {
+    fn default() -> DifficultySchedule {
+        DifficultySchedule {
+            initial_difficulty: 2,
+            retarget_interval: 10,
+            target_block_time: 10,
+        }
+    }
+}
+
+impl DifficultySchedule {
+    /// Get the difficulty required for the block that follows the given chain
+    pub fn next_difficulty(&self, chain: &[Block]) -> i32 {
+        self.retarget(chain.len(), |i| (chain[i].timestamp, chain[i].difficulty))
+    }
+
+    /// Get the difficulty required for the block that follows the given headers
+    pub fn next_header_difficulty(&self, headers: &[BlockHeader]) -> i32 {
+        self.retarget(headers.len(), |i| {
+            (headers[i].timestamp, headers[i].difficulty)
+        })
+    }
+
+    /// Get the difficulty of the block at the given height, `block(i)` returns the timestamp and the difficulty of the block at height `i`
+    fn retarget(&self, height: usize, block: impl Fn(usize) -> (u32, i32)) -> i32 {
+        if height == 0 {
+            return self.initial_difficulty;
+        }
+        let (last_timestamp, last_difficulty) = block(height - 1);
+        let height = height as i32;
+        if self.retarget_interval <= 1 || height % self.retarget_interval != 0 {
+            return last_difficulty;
+        }
+
+        // compare the time spent on the last interval with the expected time
+        let (first_timestamp, _) = block((height - self.retarget_interval) as usize);
+        let actual = last_timestamp.saturating_sub(first_timestamp) as u64;
+        let expected = self.target_block_time as u64 * (self.retarget_interval as u64 - 1);
+
+        // one leading zero is 16 times more work, so only adjust when the blocks are off by more than 4 times
+        if actual * 4 < expected {
+            (last_difficulty + 1).min(64)
+        } else if actual > expected * 4 {
+            (last_difficulty - 1).max(1)
+        } else {
+            last_difficulty
+        }
+    }
+}
+
+/// The block reward schedule
+/// The subsidy paid to the miner of a block is halved every `halving_interval` blocks
+#[derive(Clone, Debug)]
+pub struct RewardSchedule {
+    pub initial_subsidy: i64,
+    pub halving_interval: i32,
+}
+
+impl Default for RewardSchedule {
+    fn default() -> RewardSchedule {
+        RewardSchedule {
+            initial_subsidy: 50,
+            halving_interval: 1000,
+        }
+    }
+}
+
+impl RewardSchedule {
+    /// Get the subsidy of the block at the given height
+    pub fn subsidy(&self, height: i32) -> i64 {
+        let halvings = height / self.halving_interval;
+        if halvings >= 63 {
+            return 0;
+        }
+        self.initial_subsidy >> halvings
+    }
 }
 
 /// Implement the BlockchainNetwork struct
 /// This struct will hold the list of nodes and the blockchain
 impl Blockchain {
     pub fn new() -> Blockchain {
+        Blockchain::with_params(ChainParams::default(), Vec::new())
+    }
+
+    /// Create a blockchain that starts from the genesis block of the network, where the given accounts hold an initial balance
+    pub fn with_params(params: ChainParams, allocations: Vec<(String, i64)>) -> Blockchain {
+        let mut ledger = Ledger::new(&allocations);
+        ledger.apply_block(&params.genesis);
         Blockchain {
+            mempool: Mempool::default(),
+            chain: vec![params.genesis.clone()],
+            tree: BlockTree::default(),
+            params,
+            ledger,
+            allocations,
+            storage: None,
+            transactions_changed: false,
+            events: broadcast::channel(REORG_EVENT_CAPACITY).0,
+        }
+    }
+
+    /// Subscribe to the changes of the active chain
+    pub fn subscribe(&self) -> broadcast::Receiver<ReorgEvent> {
+        self.events.subscribe()
+    }
+
+    /// Load the chain and the transaction pool from the block store, and keep them stored from now on
+    pub fn open_storage(&mut self, mut storage: BlockStore) -> io::Result<()> {
+        let chain = storage.read_chain()?;
+        match chain.first() {
+            Some(genesis) if *genesis != self.params.genesis => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "The stored blockchain belongs to another network",
+                ));
+            }
+            Some(_) => {}
+            None => storage.write_blocks(0, &self.chain)?,
+        }
+        for block in chain.into_iter().skip(1) {
+            if !self.push_block(block)? {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "The stored blockchain overspends account balances",
+                ));
+            }
+        }
+        for transaction in storage.read_transactions()? {
+            let _ = self.add_transaction(transaction);
+        }
+        self.storage = Some(storage);
+        Ok(())
+    }
+
+    /// Get the accumulated proof of work of the chain
+    pub fn total_work(&self) -> u128 {
+        chain_work(&self.chain)
+    }
+
+    /// Get the difficulty required for the next block of the chain
+    pub fn next_difficulty(&self) -> i32 {
+        self.params.difficulty.next_difficulty(&self.chain)
+    }
+
+    /// Get the transactions of the next block, the coinbase paying the reward and the fees to the miner first
+    /// The pooled transactions paying the most fee per byte are selected, within the maximum block size and transaction count
+    pub fn block_transactions(&self, miner_address: &str) -> Vec<Transaction> {
+        let height = self.chain.len() as i32;
+        let pool = self.mempool.to_vec();
+        // the largest coinbase, as it pays the fees of every pooled transaction
+        let coinbase = Transaction::coinbase(miner_address, height, &self.params.reward, &pool);
+        let header = Block {
+            id: height,
+            hash: "0".repeat(64),
+            prev_hash: "0".repeat(64),
+            merkle_root: "0".repeat(64),
+            nonce: i32::MAX,
+            timestamp: u32::MAX,
+            difficulty: 64,
             transactions: Vec::new(),
-            chain: Vec::new(),
-            difficulty: 2,
+        };
+        let reserved = header.encoded_len() + transaction_size(&coinbase);
+        let mut transactions = select_transactions(
+            &pool,
+            self.params.max_block_size.saturating_sub(reserved),
+            self.params.max_block_transactions.saturating_sub(1),
+        );
+        let coinbase =
+            Transaction::coinbase(miner_address, height, &self.params.reward, &transactions);
+        transactions.insert(0, coinbase);
+        transactions
+    }
+
+    /// Append a block to the end of the chain, update the ledger and remove its transactions from the pool
+    /// Return false if the block does not follow the last block or overspends any account
+    /// Return an error and keep the current chain if the block could not be stored
+    pub fn push_block(&mut self, block: Block) -> io::Result<bool> {
+        let prev_hash = self.chain.last().map(|b| b.hash.as_str()).unwrap_or("");
+        if block.prev_hash != prev_hash || !self.ledger.apply_block(&block) {
+            return Ok(false);
         }
+        if let Err(e) = self.persist(self.chain.len(), std::slice::from_ref(&block)) {
+            self.ledger.revert_block(&block);
+            return Err(e);
+        }
+        self.mempool.remove_included(&block.transactions);
+        self.chain.push(block.clone());
+        self.prune_transactions();
+        self.emit(ReorgEvent {
+            fork_height: self.chain.len() - 1,
+            disconnected: Vec::new(),
+            connected: vec![block],
+        });
+        Ok(true)
+    }
+
+    /// Replace the current chain with a new chain, only the blocks after the common prefix are reverted and applied to the ledger
+    /// The transactions of the removed blocks go back to the pool if the new chain does not include them and they are still affordable
+    /// Return false and keep the current chain if any block of the new chain overspends an account
+    /// Return an error and keep the current chain if the new blocks could not be stored
+    pub fn replace_chain(&mut self, chain: Vec<Block>) -> io::Result<bool> {
+        if chain.first() != Some(&self.params.genesis) {
+            return Ok(false);
+        }
+        let common = self
+            .chain
+            .iter()
+            .zip(chain.iter())
+            .take_while(|(a, b)| a.hash == b.hash)
+            .count();
+        let mut ledger = self.ledger.clone();
+        for block in self.chain[common..].iter().rev() {
+            ledger.revert_block(block);
+        }
+        for block in chain[common..].iter() {
+            if !ledger.apply_block(block) {
+                return Ok(false);
+            }
+        }
+        self.persist(common, &chain[common..])?;
+
+        // remove the transactions that are included in the new blocks
+        let included: Vec<&Transaction> = chain[common..]
+            .iter()
+            .flat_map(|block| block.transactions.iter())
+            .collect();
+        for block in &chain[common..] {
+            self.mempool.remove_included(&block.transactions);
+        }
+
+        // return the transactions of the disconnected blocks to the pool, ahead of the newer pooled transactions
+        let disconnected = self.chain.split_off(common);
+        let transactions: Vec<Transaction> = disconnected
+            .iter()
+            .flat_map(|block| block.transactions.iter())
+            .filter(|tx| !tx.is_coinbase() && !included.contains(tx))
+            .filter(|tx| tx.check_transaction_validity())
+            .cloned()
+            .collect();
+        self.mempool.reinsert(transactions);
+
+        // the disconnected blocks become a side branch, and the connected blocks leave the side branches
+        for block in &disconnected {
+            self.tree.insert(block.clone());
+        }
+        for block in &chain[common..] {
+            self.tree.remove(&block.hash);
+        }
+        self.ledger = ledger;
+        self.chain = chain;
+        self.prune_transactions();
+        self.prune_side_branches();
+        self.emit(ReorgEvent {
+            fork_height: common,
+            disconnected,
+            connected: self.chain[common..].to_vec(),
+        });
+        Ok(true)
     }
+
+    /// Add a transaction to the transaction pool if the sender can afford it on top of its pending transactions
+    /// Return the pending transaction it replaced, or the reason the pool refused the transaction
+    pub fn add_transaction(
+        &mut self,
+        transaction: Transaction,
+    ) -> Result<Option<Transaction>, RejectReason> {
+        let replaced = self.mempool.insert(transaction, &self.ledger)?;
+        self.transactions_changed = true;
+        Ok(replaced)
+    }
+
+    /// Get the nonce of the next transaction of an account, after its pending transactions
+    pub fn next_nonce(&self, account: &str) -> u64 {
+        self.mempool.next_nonce(account, &self.ledger)
+    }
+
+    /// Drop the pooled transactions that waited longer than the maximum age of the pool
+    /// Return the number of dropped transactions
+    pub fn expire_transactions(&mut self) -> usize {
+        let expired = self.mempool.expire();
+        if expired > 0 {
+            self.transactions_changed = true;
+        }
+        expired
+    }
+
+    /// Find the chain that ends with the block with the given hash at the given height, on the active chain or on a side branch
+    /// Return the height where the chain forks from the active chain and the blocks of its side branch after the fork,
+    /// so only the side branch is copied, or None if the block is not known at that height
+    pub fn fork(&self, hash: &str, height: usize) -> Option<(usize, Vec<Block>)> {
+        if self
+            .chain
+            .get(height)
+            .is_some_and(|block| block.hash == hash)
+        {
+            return Some((height + 1, Vec::new()));
+        }
+        let branch = self.tree.branch(hash);
+        let first = branch.first()?;
+        let fork = usize::try_from(first.id).ok()?;
+        let joins_chain = fork
+            .checked_sub(1)
+            .and_then(|parent| self.chain.get(parent))
+            .is_some_and(|parent| parent.hash == first.prev_hash);
+        (joins_chain && fork + branch.len() == height + 1).then_some((fork, branch))
+    }
+
+    /// Keep valid blocks that do not extend the active chain, so the node can switch to their branch once it has more work
+    /// The blocks already on the active chain are skipped, they are only kept in the chain
+    pub fn add_side_blocks(&mut self, blocks: Vec<Block>) {
+        for block in blocks {
+            let on_chain = usize::try_from(block.id)
+                .ok()
+                .and_then(|height| self.chain.get(height))
+                .is_some_and(|active| active.hash == block.hash);
+            if !on_chain {
+                self.tree.insert(block);
+            }
+        }
+        self.prune_side_branches();
+    }
+
+    /// Drop the blocks of side branches that are too deep below the tip of the active chain
+    fn prune_side_branches(&mut self) {
+        let min_height = self.chain.len() as i32 - MAX_SIDE_BRANCH_DEPTH;
+        self.tree.retain(|block| block.id >= min_height);
+    }
+
+    /// Notify the subscribers of a change of the active chain
+    fn emit(&self, event: ReorgEvent) {
+        // there may be no subscriber, the event is dropped then
+        let _ = self.events.send(event);
+    }
+
+    /// Write the blocks that replace the chain from the given height to the block store, before the chain changes
+    /// The transaction pool changes with them and is written later
+    fn persist(&mut self, start: usize, blocks: &[Block]) -> io::Result<()> {
+        if let Some(storage) = &mut self.storage {
+            storage.write_blocks(start, blocks)?;
+        }
+        self.transactions_changed = true;
+        Ok(())
+    }
+
+    /// Get the write of the transaction pool to the block store if the pool changed since the last one
+    /// The write runs without the blockchain, so the disk does not hold up the transactions and the blocks
+    pub fn transactions_write(&mut self) -> Option<TransactionsWrite> {
+        let storage = self.storage.as_ref()?;
+        if !self.transactions_changed {
+            return None;
+        }
+        self.transactions_changed = false;
+        Some(storage.transactions_write(&self.mempool.to_vec()))
+    }
+
+    /// Drop the pooled transactions that can no longer be afforded with the current balances
+    fn prune_transactions(&mut self) {
+        self.mempool.prune(&self.ledger);
+    }
+
     /// Check if the block is valid
     pub async fn check_blockchain_validity(&self) -> bool {
-        let mut current_timestamp = 0;
+        self.first_invalid_block().is_none()
+    }
+
+    /// Get the height of the first invalid block of the chain
+    pub fn first_invalid_block(&self) -> Option<usize> {
+        // the genesis block is hard-coded, so it is compared instead of validated
+        if self.chain.first() != Some(&self.params.genesis) {
+            return Some(0);
+        }
         let mut prev_hash = "".to_string();
+        let mut ledger = Ledger::new(&self.allocations);
         for (i, block) in self.chain.iter().enumerate() {
             if i != block.id as usize {
-                return false;
+                return Some(i);
             }
 
             if block.prev_hash != prev_hash {
-                return false;
+                return Some(i);
             }
 
-            if block.timestamp <= current_timestamp {
-                return false;
+            if i > 0 && !check_timestamp_validity(&self.chain[..i], block.timestamp) {
+                return Some(i);
             }
-            if !block.check_block_validity() {
-                return false;
+
+            if i > 0 && block.difficulty != self.params.difficulty.next_difficulty(&self.chain[..i])
+            {
+                return Some(i);
+            }
+            if i > 0 && !block.check_block_validity(&self.params) {
+                return Some(i);
+            }
+            if !ledger.apply_block(block) {
+                return Some(i);
             }
-            current_timestamp = block.timestamp;
             prev_hash = block.hash.clone();
         }
-        true
+        None
+    }
+
+    /// Import a chain into the blockchain, every block of the chain is validated before it replaces the current chain
+    /// Return the height of the first invalid block, or the error of the block store, and keep the current chain on failure
+    pub fn import_chain(&mut self, chain: Vec<Block>) -> Result<(), ImportError> {
+        let mut imported = Blockchain::with_params(self.params.clone(), self.allocations.clone());
+        imported.chain = chain;
+        if let Some(height) = imported.first_invalid_block() {
+            return Err(ImportError::InvalidBlock(height));
+        }
+        if !self.replace_chain(imported.chain)? {
+            return Err(ImportError::InvalidBlock(0));
+        }
+        Ok(())
     }
 }
 
 impl Block {
-    /// Compute the hash of the block
+    /// Compute the hash of the block header
+    /// The header commits to the transactions through the merkle root, so its size does not depend on the number of transactions
     pub fn compute_hash(&self, nonce: i32) -> String {
-        let mut tx_hashes = "".to_string();
-        for (i, transaction) in self.transactions.iter().enumerate() {
-            if i < self.transactions.len() - 1 {
-                tx_hashes.push_str(&transaction.compute_hash());
-                tx_hashes.push_str("|");
-            } else {
-                tx_hashes.push_str(&transaction.compute_hash());
-            }
+        hash_header(
+            self.id,
+            &self.prev_hash,
+            &self.merkle_root,
+            self.timestamp,
+            self.difficulty,
+            nonce,
+        )
+    }
+
+    /// Get the data of the header that is hashed before the nonce, the mining kernels append the nonce to it
+    pub fn header_data(&self) -> String {
+        header_data(
+            self.id,
+            &self.prev_hash,
+            &self.merkle_root,
+            self.timestamp,
+            self.difficulty,
+        )
+    }
+
+    /// Get the header of the block
+    pub fn header(&self) -> BlockHeader {
+        BlockHeader {
+            id: self.id,
+            hash: self.hash.clone(),
+            prev_hash: self.prev_hash.clone(),
+            nonce: self.nonce,
+            timestamp: self.timestamp,
+            difficulty: self.difficulty,
+            merkle_root: self.merkle_root.clone(),
         }
-        let data = format!("{}|{}|{}", self.id, self.prev_hash, tx_hashes);
-        let hex_hash = sha_hash(&data);
-        let input = hex_hash + nonce.to_string().as_str();
-        sha_hash(&input)
+    }
+
+    /// Compute the merkle root of the transactions in the block
+    pub fn compute_merkle_root(&self) -> String {
+        let hashes: Vec<String> = self
+            .transactions
+            .iter()
+            .map(|transaction| transaction.hash.clone())
+            .collect();
+        merkle_root(&hashes)
     }
 
     /// Check if the block is valid
-    pub fn check_block_validity(&self) -> bool {
+    pub fn check_block_validity(&self, params: &ChainParams) -> bool {
         if self.id < 0 {
             return false;
         }
 
+        if self.encoded_len() > params.max_block_size
+            || self.transactions.len() > params.max_block_transactions
+        {
+            return false;
+        }
+
+        if self.merkle_root != self.compute_merkle_root() {
+            return false;
+        }
+
         if self.hash != self.compute_hash(self.nonce) {
             return false;
         }
 
+        if !check_hash_validity(&self.hash, self.difficulty) {
+            return false;
+        }
+
+        // the first transaction must be the coinbase, paying the subsidy and the fees to the miner
+        let (coinbase, transactions) = match self.transactions.split_first() {
+            Some(split) => split,
+            None => return false,
+        };
+        if !coinbase.check_coinbase_validity() {
+            return false;
+        }
+        let fees: i64 = transactions.iter().map(|tx| tx.fee as i64).sum();
+        if coinbase.amount as i64 != params.reward.subsidy(self.id) + fees {
+            return false;
+        }
+
         // check if timestamp is valid
         let mut current_timestamp = 0;
-        for transaction in self.transactions.iter() {
+        for transaction in transactions.iter() {
+            if transaction.is_coinbase() {
+                return false;
+            }
             if current_timestamp >= transaction.timestamp {
                 return false;
             }
@@ -94,12 +574,120 @@ impl Block {
     }
 }
 
+impl BlockHeader {
+    /// Compute the hash of the block header
+    pub fn compute_hash(&self, nonce: i32) -> String {
+        hash_header(
+            self.id,
+            &self.prev_hash,
+            &self.merkle_root,
+            self.timestamp,
+            self.difficulty,
+            nonce,
+        )
+    }
+
+    /// Check if the header hash is correct and has the required proof of work
+    pub fn check_header_validity(&self) -> bool {
+        self.id >= 0
+            && self.hash == self.compute_hash(self.nonce)
+            && check_hash_validity(&self.hash, self.difficulty)
+    }
+}
+
+/// Check if the headers are a valid chain starting from the first block, every header follows the previous one and has a valid proof of work
+pub fn check_header_chain(headers: &[BlockHeader]) -> bool {
+    let mut prev_hash = "";
+    for (i, header) in headers.iter().enumerate() {
+        if header.id as usize != i || header.prev_hash != prev_hash {
+            return false;
+        }
+        if !header.check_header_validity() {
+            return false;
+        }
+        prev_hash = &header.hash;
+    }
+    true
+}
+
+/// Hash the fields of a block header
+fn hash_header(
+    id: i32,
+    prev_hash: &str,
+    merkle_root: &str,
+    timestamp: u32,
+    difficulty: i32,
+    nonce: i32,
+) -> String {
+    let data = format!(
+        "{}{}",
+        header_data(id, prev_hash, merkle_root, timestamp, difficulty),
+        nonce
+    );
+    sha_hash(&data)
+}
+
+/// Get the data of a header that is hashed before the nonce
+fn header_data(
+    id: i32,
+    prev_hash: &str,
+    merkle_root: &str,
+    timestamp: u32,
+    difficulty: i32,
+) -> String {
+    format!(
+        "{}|{}|{}|{}|{}|",
+        id, prev_hash, merkle_root, timestamp, difficulty
+    )
+}
+
 impl Transaction {
+    /// Create the coinbase transaction of a block, which pays the subsidy and the fees of the transactions to the miner
+    pub fn coinbase(
+        miner_address: &str,
+        height: i32,
+        reward: &RewardSchedule,
+        transactions: &[Transaction],
+    ) -> Transaction {
+        let fees: i64 = transactions.iter().map(|tx| tx.fee as i64).sum();
+        let mut coinbase = Transaction {
+            id: Uuid::new_v4().to_string(),
+            sender: "".to_string(),
+            receiver: miner_address.to_string(),
+            amount: (reward.subsidy(height) + fees) as i32,
+            fee: 0,
+            timestamp: SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap()
+                .as_secs() as u32,
+            hash: "".to_string(),
+            signature: "".to_string(),
+            nonce: 0,
+        };
+        coinbase.hash = coinbase.compute_hash();
+        coinbase
+    }
+
+    /// Check if the transaction is a coinbase, a coinbase has no sender
+    pub fn is_coinbase(&self) -> bool {
+        self.sender.is_empty()
+    }
+
+    /// Check if the coinbase transaction is valid
+    pub fn check_coinbase_validity(&self) -> bool {
+        self.is_coinbase()
+            && !self.receiver.is_empty()
+            && self.amount >= 0
+            && self.fee == 0
+            && self.signature.is_empty()
+            && self.hash == self.compute_hash()
+    }
+
     /// Compute the hash of the transaction
     pub fn compute_hash(&self) -> String {
         let data = format!(
-            "{}|{}|{}|{}|{}",
-            self.timestamp, self.sender, self.receiver, self.amount, self.fee
+            "{}|{}|{}|{}|{}|{}",
+            self.timestamp, self.sender, self.receiver, self.amount, self.fee, self.nonce
         );
         sha_hash(&data)
     }
@@ -150,8 +738,81 @@ pub fn sha_hash(data: &str) -> String {
     hash.to_string()
 }
 
+/// Get the number of bytes a transaction adds to an encoded block
+pub(crate) fn transaction_size(transaction: &Transaction) -> usize {
+    Block {
+        transactions: vec![transaction.clone()],
+        ..Block::default()
+    }
+    .encoded_len()
+}
+
+/// Get the expected number of hashes needed to mine a block at the given difficulty
+/// Every leading zero of the hex hash is 16 times more work
+pub fn block_work(difficulty: i32) -> u128 {
+    1u128
+        .checked_shl(4 * difficulty.max(0) as u32)
+        .unwrap_or(u128::MAX)
+}
+
+/// Get the accumulated proof of work of a chain
+pub fn chain_work(chain: &[Block]) -> u128 {
+    chain.iter().fold(0u128, |work, block| {
+        work.saturating_add(block_work(block.difficulty))
+    })
+}
+
+/// Get the current time as a block timestamp
+fn current_timestamp() -> u32 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as u32
+}
+
+/// Get the median timestamp of the last `MEDIAN_TIME_SPAN` blocks of the chain
+pub fn median_time_past(chain: &[Block]) -> u32 {
+    let start = chain.len().saturating_sub(MEDIAN_TIME_SPAN);
+    median_timestamp(chain[start..].iter().map(|b| b.timestamp).collect())
+}
+
+/// Get the median timestamp of the last `MEDIAN_TIME_SPAN` headers of the chain
+pub fn header_median_time_past(headers: &[BlockHeader]) -> u32 {
+    let start = headers.len().saturating_sub(MEDIAN_TIME_SPAN);
+    median_timestamp(headers[start..].iter().map(|h| h.timestamp).collect())
+}
+
+/// Get the median of the timestamps, 0 if there is none
+fn median_timestamp(mut timestamps: Vec<u32>) -> u32 {
+    if timestamps.is_empty() {
+        return 0;
+    }
+    timestamps.sort_unstable();
+    timestamps[timestamps.len() / 2]
+}
+
+/// Check if the timestamp of a block that follows the chain is after the median time past and not too far in the future
+pub fn check_timestamp_validity(chain: &[Block], timestamp: u32) -> bool {
+    timestamp > median_time_past(chain) && !is_future_timestamp(timestamp)
+}
+
+/// Check if the timestamp of a header that follows the headers is after the median time past and not too far in the future
+pub fn check_header_timestamp_validity(headers: &[BlockHeader], timestamp: u32) -> bool {
+    timestamp > header_median_time_past(headers) && !is_future_timestamp(timestamp)
+}
+
+/// Check if a timestamp is further in the future than a block can be
+fn is_future_timestamp(timestamp: u32) -> bool {
+    timestamp > current_timestamp().saturating_add(MAX_FUTURE_BLOCK_TIME)
+}
+
+/// Get the timestamp of a new block that follows the chain, the current time unless it is not after the median time past
+pub fn next_timestamp(chain: &[Block]) -> u32 {
+    current_timestamp().max(median_time_past(chain) + 1)
+}
+
 /// check if the hash has the required number of leading zeros
-fn check_hash_validity(hash: &String, difficulty: i32) -> bool {
+pub(crate) fn check_hash_validity(hash: &String, difficulty: i32) -> bool {
     let mut count = 0;
     for c in hash.chars() {
         if c == '0' {
@@ -163,54 +824,38 @@ fn check_hash_validity(hash: &String, difficulty: i32) -> bool {
     count >= difficulty
 }
 
-/// Mine a new block
-pub async fn mine_new_block(
+/// Build the block that follows the last block, everything but its nonce and hash is set
+pub fn block_template(
     last_block: &Block,
     transactions: Vec<Transaction>,
     difficulty: i32,
-    rx: &mut Receiver<bool>,
-) -> Result<Block, Status> {
-    println!("[INFO] Mining new block");
-    let mut nonce = 0;
-
+    timestamp: u32,
+) -> Block {
+    // the timestamp is part of the header, so it is chosen before mining
     let mut new_block = Block {
         id: last_block.id + 1,
-        timestamp: 0,
+        timestamp,
         prev_hash: last_block.hash.clone(),
         hash: "".to_string(),
         nonce: 0,
-        difficulty: difficulty,
-        transactions: transactions.clone(),
+        difficulty,
+        transactions,
+        merkle_root: "".to_string(),
     };
+    new_block.merkle_root = new_block.compute_merkle_root();
+    new_block
+}
 
-    let mut current_hash = new_block.compute_hash(nonce);
-
-    while !check_hash_validity(&current_hash, difficulty) {
-        // if received a signal to stop mining, then return an error
-        if let Ok(_) = rx.try_recv() {
-            println!("[INFO] Mining interrupted");
-            return Err(Status::cancelled("Mining stopped"));
-        }
-
-        nonce += 1;
-        current_hash = new_block.compute_hash(nonce);
-        if nonce % 1000 == 0 {
-            tokio::task::yield_now().await;
-        }
-    }
-
-    new_block.nonce = nonce;
-    new_block.hash = current_hash.clone();
-    new_block.timestamp = SystemTime::now()
-        .duration_since(SystemTime::UNIX_EPOCH)
-        .unwrap()
-        .as_secs() as u32;
-
-    println!(
-        "[INFO] New block mined: id = {:?}, hash = {:?}",
-        new_block.id, new_block.hash
-    );
-
-    // send the new block to the rest of the network
-    Ok(new_block)
+/// Mine a new block with the default mining backend
+pub async fn mine_new_block(
+    last_block: &Block,
+    transactions: Vec<Transaction>,
+    difficulty: i32,
+    timestamp: u32,
+    rx: &mut Receiver<bool>,
+) -> Result<Block, Status> {
+    let block = block_template(last_block, transactions, difficulty, timestamp);
+    Miner::new(default_backend(default_threads()))
+        .mine_block(block, rx)
+        .await
 }
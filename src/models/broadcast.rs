@@ -0,0 +1,239 @@
+//! ## Broadcast module
+//! The broadcaster sends the new blocks and transactions to the peers without holding the blockchain lock
+//! The blocks are sent from a snapshot of the chain, taken when the block is added, so the node keeps mining and answering while they are sent
+//! Every peer has its own outbound queue, sent in order by its own task, so a slow or hung peer only delays its own messages
+//! The sends to all the peers share a limit, so a large peer list does not open an unbounded number of requests at once
+//! A send that takes longer than the slow send timeout gives up its place in the limit, so hung peers cannot hold it
+//! A message is dropped with a warning when the queue of its peer is full, the peer gets the later blocks anyway
+use crate::models::blockchain::Blockchain;
+use crate::models::peers::PeerManager;
+use crate::node::{
+    Block, NodeInfo, Transaction, UpdateBlockchainRequest, UpdateTransactionRequest,
+};
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::sync::{mpsc, oneshot, Semaphore};
+use tonic::Request;
+
+/// The default number of messages waiting to be sent to a peer
+pub const MAX_QUEUED_MESSAGES: usize = 64;
+/// The default number of requests sent to the peers at once
+pub const MAX_CONCURRENT_SENDS: usize = 8;
+/// The time a send holds its place in the limit of the requests sent at once, a slower send goes on without it
+pub const SLOW_SEND_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// The blocks of the active chain and their work, as they were when the snapshot was taken
+pub struct ChainSnapshot {
+    pub chain: Vec<Block>,
+    pub total_work: u128,
+}
+
+impl ChainSnapshot {
+    pub fn new(blockchain: &Blockchain) -> ChainSnapshot {
+        ChainSnapshot {
+            chain: blockchain.chain.clone(),
+            total_work: blockchain.total_work(),
+        }
+    }
+}
+
+/// A message waiting in the queue of a peer
+#[derive(Clone)]
+pub enum Outbound {
+    /// The new tip of the chain, with the earlier blocks the peer may be missing
+    Chain(Arc<ChainSnapshot>),
+    Transactions(Arc<Vec<Transaction>>),
+}
+
+struct Queued {
+    peer: NodeInfo,
+    message: Outbound,
+    /// Told when the message was sent, or failed to be sent
+    done: oneshot::Sender<()>,
+}
+
+/// The messages of a broadcast, which can be waited on until every peer was sent its message or failed
+#[derive(Default)]
+pub struct Delivery {
+    pending: Vec<oneshot::Receiver<()>>,
+}
+
+impl Delivery {
+    /// Wait until every peer was sent the message, or failed to be sent it
+    pub async fn wait(self) {
+        for pending in self.pending {
+            let _ = pending.await;
+        }
+    }
+}
+
+pub struct Broadcaster {
+    /// The node sending the messages, which the peers request the missing blocks from
+    node: NodeInfo,
+    peer_manager: Arc<PeerManager>,
+    max_queued_messages: usize,
+    sends: Arc<Semaphore>,
+    /// The queue of every peer, keyed by node id
+    queues: Mutex<HashMap<String, mpsc::Sender<Queued>>>,
+}
+
+impl Broadcaster {
+    pub fn new(
+        node: NodeInfo,
+        peer_manager: Arc<PeerManager>,
+        max_queued_messages: usize,
+        max_concurrent_sends: usize,
+    ) -> Broadcaster {
+        Broadcaster {
+            node,
+            peer_manager,
+            max_queued_messages,
+            sends: Arc::new(Semaphore::new(max_concurrent_sends)),
+            queues: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Queue a message for every peer except this node, without waiting for it to be sent
+    pub fn broadcast(&self, peers: &[NodeInfo], message: Outbound) -> Delivery {
+        let mut delivery = Delivery::default();
+        for peer in peers {
+            if peer.id == self.node.id {
+                continue;
+            }
+            if let Some(pending) = self.send(peer, message.clone()) {
+                delivery.pending.push(pending);
+            }
+        }
+        delivery
+    }
+
+    /// Queue a message for a peer, starting the task of the peer on its first message
+    /// The message is dropped if the queue of the peer is full
+    fn send(&self, peer: &NodeInfo, message: Outbound) -> Option<oneshot::Receiver<()>> {
+        let (done, pending) = oneshot::channel();
+        let mut queued = Queued {
+            peer: peer.clone(),
+            message,
+            done,
+        };
+        let mut queues = self.queues.lock().unwrap();
+        loop {
+            let queue = queues
+                .entry(peer.id.clone())
+                .or_insert_with(|| self.spawn_worker());
+            match queue.try_send(queued) {
+                Ok(()) => return Some(pending),
+                Err(TrySendError::Full(_)) => {
+                    println!(
+                        "[Warning] Outbound queue of node {} is full, dropping message",
+                        peer.url()
+                    );
+                    return None;
+                }
+                // the task of the peer stopped, so it is started again
+                Err(TrySendError::Closed(message)) => {
+                    queues.remove(&peer.id);
+                    queued = message;
+                }
+            }
+        }
+    }
+
+    /// Start the task sending the queued messages of a peer in order
+    fn spawn_worker(&self) -> mpsc::Sender<Queued> {
+        let (tx, mut rx) = mpsc::channel::<Queued>(self.max_queued_messages);
+        let node = self.node.clone();
+        let peer_manager = self.peer_manager.clone();
+        let sends = self.sends.clone();
+        tokio::spawn(async move {
+            while let Some(queued) = rx.recv().await {
+                match queued.message {
+                    Outbound::Chain(snapshot) => {
+                        send_chain(&peer_manager, &sends, &node, &queued.peer, &snapshot).await
+                    }
+                    Outbound::Transactions(transactions) => {
+                        send_transactions(&peer_manager, &sends, &queued.peer, &transactions).await
+                    }
+                }
+                let _ = queued.done.send(());
+            }
+        });
+        tx
+    }
+}
+
+/// Send the tip of the chain to a peer
+/// A peer that is missing earlier blocks is sent the blocks from the end of its chain, until it has at least as much work
+async fn send_chain(
+    peer_manager: &PeerManager,
+    sends: &Semaphore,
+    node: &NodeInfo,
+    peer: &NodeInfo,
+    snapshot: &ChainSnapshot,
+) {
+    let mut start_idx = snapshot.chain.len() - 1;
+    loop {
+        let request = Request::new(UpdateBlockchainRequest {
+            blocks: snapshot.chain[start_idx..].to_vec(),
+            node: Some(node.clone()),
+        });
+        let call = peer_manager.call(peer, |mut client| async move {
+            client.update_blockchain(request).await
+        });
+        match limit(sends, call).await {
+            Ok(response) => {
+                // stop when the peer accepted the blocks, or already has a chain with at least as much work
+                let peer_work = response.total_work.parse::<u128>().unwrap_or(0);
+                if response.success || peer_work >= snapshot.total_work {
+                    return;
+                }
+                start_idx = (response.chain_length as usize).min(snapshot.chain.len() - 1);
+            }
+            Err(error) => {
+                println!(
+                    "[Warning] Failed to update blockchain on node: {}, {}",
+                    peer.url(),
+                    error.message()
+                );
+                return;
+            }
+        }
+    }
+}
+
+/// Relay transactions to a peer
+async fn send_transactions(
+    peer_manager: &PeerManager,
+    sends: &Semaphore,
+    peer: &NodeInfo,
+    transactions: &[Transaction],
+) {
+    let request = Request::new(UpdateTransactionRequest {
+        transactions: transactions.to_vec(),
+    });
+    let call = peer_manager.call(peer, |mut client| async move {
+        client.update_transaction(request).await
+    });
+    let result = limit(sends, call).await;
+    if let Err(error) = result {
+        println!(
+            "[Warning] Failed to send transactions to node: {}, {}",
+            peer.url(),
+            error.message()
+        );
+    }
+}
+
+/// Run a send in the limit of the requests sent at once, the send gives up its place after the slow send timeout
+async fn limit<F: Future>(sends: &Semaphore, send: F) -> F::Output {
+    let permit = sends.acquire().await;
+    tokio::pin!(send);
+    if let Ok(output) = tokio::time::timeout(SLOW_SEND_TIMEOUT, &mut send).await {
+        return output;
+    }
+    drop(permit);
+    send.await
+}
@@ -0,0 +1,156 @@
+//! ## Chain parameters module
+//! The chain parameters are the consensus rules that every node of a network must agree on
+//! Each network starts from its own hard-coded genesis block, which is verified against its known hash
+//! Nodes of different networks refuse to join each other, so their blockchains never mix
+use crate::models::blockchain::{DifficultySchedule, RewardSchedule};
+use crate::node::{Block, Transaction};
+
+/// The default maximum size of an encoded block, in bytes
+pub const MAX_BLOCK_SIZE: usize = 1_000_000;
+/// The default maximum number of transactions in a block, the coinbase included
+pub const MAX_BLOCK_TRANSACTIONS: usize = 2_000;
+/// The default maximum number of blocks mined by one GenerateBlocks request
+pub const MAX_GENERATED_BLOCKS: u32 = 1_000;
+
+#[derive(Clone, Debug)]
+pub struct ChainParams {
+    /// The name of the network
+    pub name: String,
+    /// The number that identifies the network, sent when joining a peer
+    pub network_magic: u32,
+    /// The first block of the chain, every chain of the network starts with it
+    pub genesis: Block,
+    /// The initial difficulty, the target block interval and the retarget schedule
+    pub difficulty: DifficultySchedule,
+    /// The block reward schedule
+    pub reward: RewardSchedule,
+    /// The maximum size of an encoded block, in bytes
+    pub max_block_size: usize,
+    /// The maximum number of transactions in a block, the coinbase included
+    pub max_block_transactions: usize,
+    /// Only mine blocks on GenerateBlocks requests, instead of whenever the transaction pool is not empty
+    pub mine_on_demand: bool,
+    /// The maximum number of blocks mined by one GenerateBlocks request
+    pub max_generated_blocks: u32,
+}
+
+impl Default for ChainParams {
+    fn default() -> ChainParams {
+        ChainParams::mainnet()
+    }
+}
+
+impl ChainParams {
+    /// The parameters of the main network
+    pub fn mainnet() -> ChainParams {
+        ChainParams {
+            name: "mainnet".to_string(),
+            network_magic: 0xB0BA_0001,
+            genesis: genesis_block(
+                "mainnet",
+                1_717_200_000,
+                2,
+                87,
+                "00cb98eba9feb5e6c5946c28a3cd83750e7f7e2a0b10276f1601c1cfd8fa8c88",
+            ),
+            difficulty: DifficultySchedule::default(),
+            reward: RewardSchedule::default(),
+            max_block_size: MAX_BLOCK_SIZE,
+            max_block_transactions: MAX_BLOCK_TRANSACTIONS,
+            mine_on_demand: false,
+            max_generated_blocks: MAX_GENERATED_BLOCKS,
+        }
+    }
+
+    /// The parameters of the test network, which has the same rules as the main network but a different genesis block
+    pub fn testnet() -> ChainParams {
+        ChainParams {
+            name: "testnet".to_string(),
+            network_magic: 0xB0BA_0002,
+            genesis: genesis_block(
+                "testnet",
+                1_717_200_000,
+                2,
+                223,
+                "0067a767f48af4e715770785b1784954315ad8ec4e0d8217fa417284d9c333f6",
+            ),
+            difficulty: DifficultySchedule::default(),
+            reward: RewardSchedule::default(),
+            max_block_size: MAX_BLOCK_SIZE,
+            max_block_transactions: MAX_BLOCK_TRANSACTIONS,
+            mine_on_demand: false,
+            max_generated_blocks: MAX_GENERATED_BLOCKS,
+        }
+    }
+
+    /// The parameters of the regression test network, where blocks are only mined on request, at the minimal difficulty without retargeting
+    pub fn regtest() -> ChainParams {
+        ChainParams {
+            name: "regtest".to_string(),
+            network_magic: 0xB0BA_0003,
+            genesis: genesis_block(
+                "regtest",
+                1_717_200_000,
+                1,
+                31,
+                "0a95ed8b0fbaebb9bfbaa8512610cea0679a4fd1c50b2be57536de736305fc47",
+            ),
+            difficulty: DifficultySchedule {
+                initial_difficulty: 1,
+                retarget_interval: 0,
+                target_block_time: 10,
+            },
+            reward: RewardSchedule {
+                initial_subsidy: 50,
+                halving_interval: 150,
+            },
+            max_block_size: MAX_BLOCK_SIZE,
+            max_block_transactions: MAX_BLOCK_TRANSACTIONS,
+            mine_on_demand: true,
+            max_generated_blocks: MAX_GENERATED_BLOCKS,
+        }
+    }
+
+    /// Get the parameters of the network with the given name
+    pub fn from_name(name: &str) -> Option<ChainParams> {
+        match name {
+            "mainnet" => Some(ChainParams::mainnet()),
+            "testnet" => Some(ChainParams::testnet()),
+            "regtest" => Some(ChainParams::regtest()),
+            _ => None,
+        }
+    }
+
+    /// Get the hash of the genesis block
+    pub fn genesis_hash(&self) -> &str {
+        &self.genesis.hash
+    }
+}
+
+/// Build the genesis block of a network and verify it against its known hash
+/// The genesis block has a single transaction, which pays nothing to an address named after the network
+fn genesis_block(network: &str, timestamp: u32, difficulty: i32, nonce: i32, hash: &str) -> Block {
+    let mut coinbase = Transaction {
+        id: format!("{} genesis", network),
+        receiver: format!("{} genesis", network),
+        timestamp,
+        ..Transaction::default()
+    };
+    coinbase.hash = coinbase.compute_hash();
+    let mut block = Block {
+        id: 0,
+        timestamp,
+        nonce,
+        difficulty,
+        transactions: vec![coinbase],
+        ..Block::default()
+    };
+    block.merkle_root = block.compute_merkle_root();
+    block.hash = block.compute_hash(nonce);
+    assert_eq!(
+        block.hash, hash,
+        "The {} genesis block does not match its hash",
+        network
+    );
+    block
+}
@@ -2,8 +2,12 @@
 //! Contains the client struct and its implementation
 //! The client struct is used to interact with the blockchain network
 //! The client can send transactions to the network
-use crate::node::UpdateTransactionRequest;
+use crate::models::archive::export_chain;
 use crate::node::{node_message_client::NodeMessageClient, Transaction};
+use crate::node::{
+    GenerateBlocksRequest, GetAccountRequest, GetBlockchainRequest, GetMiningInfoRequest,
+    GetTransactionListRequest, UpdateTransactionRequest,
+};
 use openssl::hash::MessageDigest;
 use openssl::pkey::PKey;
 use openssl::rsa::Rsa;
@@ -49,20 +53,162 @@ impl Client {
                     let mut fee = String::new();
                     std::io::stdin().read_line(&mut fee).unwrap();
                     let fee = fee.trim().parse::<i32>().unwrap();
-                    let transaction = self.generate_transaction(receiver.to_string(), amount, fee);
 
                     let mut grpc_client =
                         NodeMessageClient::connect(format!("http://127.0.0.1:{}", self.port))
                             .await
                             .expect("Failed to connect to node");
+                    // the transaction follows the pending transactions of the client
+                    let nonce = grpc_client
+                        .get_account(Request::new(GetAccountRequest {
+                            account: self.public_key.clone(),
+                        }))
+                        .await
+                        .unwrap()
+                        .into_inner()
+                        .pending_nonce;
+                    let transaction =
+                        self.generate_transaction(receiver.to_string(), amount, fee, nonce);
 
-                    grpc_client
+                    let res = grpc_client
                         .update_client_transaction(Request::new(UpdateTransactionRequest {
                             transactions: vec![transaction.clone()],
                         }))
                         .await
-                        .unwrap();
-                    println!("[INFO] Transaction sent\n\n");
+                        .unwrap()
+                        .into_inner();
+                    match res.rejected.first() {
+                        None => println!("[INFO] Transaction sent\n\n"),
+                        Some(rejected) => {
+                            println!("[ERROR] Transaction rejected: {}\n\n", rejected.reason)
+                        }
+                    }
+                }
+                "bump" => {
+                    println!("transaction id: ");
+                    let mut id = String::new();
+                    std::io::stdin().read_line(&mut id).unwrap();
+                    let id = id.trim();
+                    println!("new fee: ");
+                    let mut fee = String::new();
+                    std::io::stdin().read_line(&mut fee).unwrap();
+                    let fee = fee.trim().parse::<i32>().unwrap();
+
+                    let mut grpc_client =
+                        NodeMessageClient::connect(format!("http://127.0.0.1:{}", self.port))
+                            .await
+                            .expect("Failed to connect to node");
+                    let pending = grpc_client
+                        .get_transaction_list(Request::new(GetTransactionListRequest {}))
+                        .await
+                        .unwrap()
+                        .into_inner()
+                        .transactions
+                        .into_iter()
+                        .find(|tx| tx.id == id && tx.sender == self.public_key);
+                    let pending = match pending {
+                        Some(pending) => pending,
+                        None => {
+                            println!("[ERROR] No pending transaction with this id\n\n");
+                            continue;
+                        }
+                    };
+                    // the replacement keeps the nonce of the pending transaction, so the nodes replace it
+                    let transaction = self.sign_transaction(
+                        id.to_string(),
+                        pending.receiver,
+                        pending.amount,
+                        fee,
+                        pending.nonce,
+                    );
+                    let res = grpc_client
+                        .update_client_transaction(Request::new(UpdateTransactionRequest {
+                            transactions: vec![transaction],
+                        }))
+                        .await
+                        .unwrap()
+                        .into_inner();
+                    match res.rejected.first() {
+                        None => println!("[INFO] Transaction fee bumped\n\n"),
+                        Some(rejected) => {
+                            println!("[ERROR] Transaction rejected: {}\n\n", rejected.reason)
+                        }
+                    }
+                }
+                "export" => {
+                    println!("file: ");
+                    let mut path = String::new();
+                    std::io::stdin().read_line(&mut path).unwrap();
+                    let path = path.trim();
+
+                    let mut grpc_client =
+                        NodeMessageClient::connect(format!("http://127.0.0.1:{}", self.port))
+                            .await
+                            .expect("Failed to connect to node");
+                    let chain = grpc_client
+                        .get_blockchain(Request::new(GetBlockchainRequest {}))
+                        .await
+                        .unwrap()
+                        .into_inner()
+                        .chain;
+                    match export_chain(path, &chain) {
+                        Ok(()) => {
+                            println!("[INFO] Exported {} blocks to {}\n\n", chain.len(), path)
+                        }
+                        Err(e) => println!("[ERROR] Failed to export the chain: {}\n\n", e),
+                    }
+                }
+                "generate" => {
+                    println!("blocks: ");
+                    let mut count = String::new();
+                    std::io::stdin().read_line(&mut count).unwrap();
+                    let count = count.trim().parse::<u32>().unwrap();
+
+                    let mut grpc_client =
+                        NodeMessageClient::connect(format!("http://127.0.0.1:{}", self.port))
+                            .await
+                            .expect("Failed to connect to node");
+                    match grpc_client
+                        .generate_blocks(Request::new(GenerateBlocksRequest {
+                            count,
+                            miner_address: self.public_key.clone(),
+                        }))
+                        .await
+                    {
+                        Ok(res) => println!(
+                            "[INFO] Generated {} blocks\n\n",
+                            res.into_inner().blocks.len()
+                        ),
+                        Err(e) => {
+                            println!("[ERROR] Failed to generate blocks: {}\n\n", e.message())
+                        }
+                    }
+                }
+                "mining" => {
+                    let mut grpc_client =
+                        NodeMessageClient::connect(format!("http://127.0.0.1:{}", self.port))
+                            .await
+                            .expect("Failed to connect to node");
+                    let info = grpc_client
+                        .get_mining_info(Request::new(GetMiningInfoRequest {}))
+                        .await
+                        .unwrap()
+                        .into_inner();
+                    println!(
+                        "hashrate: {:.0} H/s (1m), {:.0} H/s (5m), {:.0} H/s (15m)",
+                        info.hashrate1m, info.hashrate5m, info.hashrate15m
+                    );
+                    println!(
+                        "hashes: {}, blocks mined: {}, interrupted: {}",
+                        info.hashes, info.blocks_mined, info.interrupted
+                    );
+                    println!(
+                        "mining: {}, last block: {}s ago\n",
+                        info.mining, info.seconds_since_last_block
+                    );
+                }
+                "address" => {
+                    println!("{}\n", self.public_key);
                 }
                 "exit" => {
                     break;
@@ -74,9 +220,26 @@ impl Client {
         }
     }
     /// Generate a transaction
-    fn generate_transaction(&self, receiver: String, amount: i32, fee: i32) -> Transaction {
+    fn generate_transaction(
+        &self,
+        receiver: String,
+        amount: i32,
+        fee: i32,
+        nonce: u64,
+    ) -> Transaction {
+        self.sign_transaction(Uuid::new_v4().to_string(), receiver, amount, fee, nonce)
+    }
+    /// Sign a transaction with the given id and nonce
+    fn sign_transaction(
+        &self,
+        id: String,
+        receiver: String,
+        amount: i32,
+        fee: i32,
+        nonce: u64,
+    ) -> Transaction {
         let mut transaction = Transaction {
-            id: Uuid::new_v4().to_string(),
+            id,
             sender: self.public_key.clone(),
             receiver: receiver,
             amount: amount,
@@ -87,6 +250,7 @@ impl Client {
                 .as_secs() as u32,
             hash: "".to_string(),
             signature: "".to_string(),
+            nonce,
         };
         let private_key_string = hex::decode(self.private_key.clone()).unwrap();
         let private_key = Rsa::private_key_from_pem(&private_key_string).unwrap();
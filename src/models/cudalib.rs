@@ -1,34 +1,156 @@
-// // if no CUDA installed, return
-// // if !cfg!(feature = "cuda") {
-// //     return Ok(());
-// include!(concat!(env!("OUT_DIR"), "/bindings.rs"));
-
-// use std::ffi::CString;
-
-// pub fn mine_block_rust(block_data: &str, difficulty: i32, max_nonce: i32) -> Option<(String, i32)> {
-//     // Convert Rust string to C-compatible string
-//     let block_data_c = CString::new(block_data).unwrap();
-
-//     // Prepare buffers for the result
-//     let mut final_hash = [0u8; 32];
-//     let mut final_nonce = 0;
-
-//     // Call the C++ function from Rust using FFI
-//     unsafe {
-//         mine_block(
-//             block_data_c.as_ptr(),
-//             block_data.len() as i32,
-//             difficulty,
-//             final_hash.as_mut_ptr(),
-//             &mut final_nonce,
-//             max_nonce,
-//         );
-//     }
-
-//     // Convert result into a Rust-compatible format
-//     if final_nonce != -1 {
-//         Some((hex::encode(final_hash), final_nonce))
-//     } else {
-//         None
-//     }
-// }
+//! ## CUDA mining module
+//! The mining kernels of `cuda_mining_lib` search a range of nonces for the lowest one that gives the header hash its difficulty
+//! The GPU miner searches the nonce space in batches of ranges, so a cancelled search stops after the running batch
+//! The GPU kernel is only built with the `cuda` feature, without it the nodes mine with the CPU miner
+//! The CPU reference of the kernel is always built, so the FFI contract of the kernels is tested without a GPU
+#[cfg(feature = "cuda")]
+use crate::models::miner::{CpuMiner, MiningBackend};
+#[cfg(feature = "cuda")]
+use crate::node::Block;
+use std::os::raw::{c_char, c_int, c_uchar};
+#[cfg(feature = "cuda")]
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+/// The nonce reported when no nonce below `max_nonce` gives the hash its difficulty
+const MINING_NO_NONCE: c_int = -1;
+/// The nonce reported when the GPU could not run the search
+#[cfg(feature = "cuda")]
+const MINING_GPU_ERROR: c_int = -2;
+/// The number of nonces the GPU miner searches between two checks of the cancel flag
+#[cfg(feature = "cuda")]
+pub const GPU_BATCH_NONCES: i32 = 1 << 24;
+
+/// The interface of the kernels in `cuda_mining_lib/include/mining.h`
+type Kernel =
+    unsafe extern "C" fn(*const c_char, c_int, c_int, *mut c_uchar, *mut c_int, c_int, c_int);
+
+extern "C" {
+    #[cfg(feature = "cuda")]
+    fn mine_block(
+        block_data: *const c_char,
+        data_len: c_int,
+        difficulty: c_int,
+        final_hash: *mut c_uchar,
+        final_nonce: *mut c_int,
+        start_nonce: c_int,
+        max_nonce: c_int,
+    );
+
+    fn mine_block_cpu(
+        block_data: *const c_char,
+        data_len: c_int,
+        difficulty: c_int,
+        final_hash: *mut c_uchar,
+        final_nonce: *mut c_int,
+        start_nonce: c_int,
+        max_nonce: c_int,
+    );
+}
+
+/// Run a kernel on the block data, return the nonce it reported and the hash as a hex string
+fn run_kernel(
+    kernel: Kernel,
+    block_data: &str,
+    difficulty: i32,
+    start_nonce: i32,
+    max_nonce: i32,
+) -> (i32, String) {
+    let mut final_hash = [0u8; 32];
+    let mut final_nonce = MINING_NO_NONCE;
+    // the kernels take the length of the data, so the data does not need a terminating nul
+    unsafe {
+        kernel(
+            block_data.as_ptr() as *const c_char,
+            block_data.len() as c_int,
+            difficulty,
+            final_hash.as_mut_ptr(),
+            &mut final_nonce,
+            start_nonce,
+            max_nonce,
+        );
+    }
+    (final_nonce, hex::encode(final_hash))
+}
+
+/// Search the nonces with the CPU reference of the kernel
+/// Return the hash and the lowest nonce from `start_nonce` to below `max_nonce` where the hash of the data followed by the nonce has `difficulty` leading zeros
+pub fn mine_block_reference(
+    block_data: &str,
+    difficulty: i32,
+    start_nonce: i32,
+    max_nonce: i32,
+) -> Option<(String, i32)> {
+    match run_kernel(
+        mine_block_cpu,
+        block_data,
+        difficulty,
+        start_nonce,
+        max_nonce,
+    ) {
+        (MINING_NO_NONCE, _) => None,
+        (nonce, hash) => Some((hash, nonce)),
+    }
+}
+
+/// Search the nonces with the GPU kernel, it finds the same nonce as the CPU reference
+/// Return an error if the GPU could not run the search
+#[cfg(feature = "cuda")]
+pub fn mine_block_gpu(
+    block_data: &str,
+    difficulty: i32,
+    start_nonce: i32,
+    max_nonce: i32,
+) -> Result<Option<(String, i32)>, String> {
+    match run_kernel(mine_block, block_data, difficulty, start_nonce, max_nonce) {
+        (MINING_GPU_ERROR, _) => Err("The GPU could not run the mining kernel".to_string()),
+        (MINING_NO_NONCE, _) => Ok(None),
+        (nonce, hash) => Ok(Some((hash, nonce))),
+    }
+}
+
+/// A miner that searches the nonces on the GPU, and mines on the CPU when the GPU is not available
+/// The GPU search checks the cancel flag after every batch of nonces
+#[cfg(feature = "cuda")]
+pub struct CudaMiner {
+    fallback: CpuMiner,
+}
+
+#[cfg(feature = "cuda")]
+impl CudaMiner {
+    pub fn new(fallback: CpuMiner) -> CudaMiner {
+        CudaMiner { fallback }
+    }
+}
+
+#[cfg(feature = "cuda")]
+impl MiningBackend for CudaMiner {
+    fn mine(
+        &self,
+        block: &Block,
+        cancel: &AtomicBool,
+        hashes: &AtomicU64,
+    ) -> Option<(i32, String)> {
+        let data = block.header_data();
+        let mut start = 0;
+        while start < i32::MAX {
+            if cancel.load(Ordering::Relaxed) {
+                return None;
+            }
+            let end = start.saturating_add(GPU_BATCH_NONCES);
+            match mine_block_gpu(&data, block.difficulty, start, end) {
+                Ok(Some((hash, nonce))) => {
+                    // the kernel tries every nonce of the batch up to the winning one
+                    hashes.fetch_add((nonce - start) as u64 + 1, Ordering::Relaxed);
+                    return Some((nonce, hash)).filter(|_| !cancel.load(Ordering::Relaxed));
+                }
+                Ok(None) => hashes.fetch_add((end - start) as u64, Ordering::Relaxed),
+                Err(e) => {
+                    println!("[Warning] {}, mining on the CPU", e);
+                    return self.fallback.mine(block, cancel, hashes);
+                }
+            };
+            start = end;
+        }
+        None
+    }
+}
@@ -0,0 +1,105 @@
+//! ## Ledger module
+//! The ledger keeps the balance of every account, keyed by the public key of the account
+//! The balances are derived from the blocks in the blockchain, and are updated incrementally when blocks are appended or removed
+//! A transaction debits `amount + fee` from the sender and credits `amount` to the receiver
+//! A coinbase transaction has no sender, it only credits the block reward to the miner
+//! The ledger also counts the transactions sent by every account, the nonce the next transaction of the account must carry
+//! The nonces of a sender strictly increase one by one across the chain, so a transaction cannot be included twice
+use crate::node::{Block, Transaction};
+use std::collections::HashMap;
+
+#[derive(Clone, Debug, Default)]
+pub struct Ledger {
+    balances: HashMap<String, i64>,
+    nonces: HashMap<String, u64>,
+}
+
+impl Ledger {
+    /// Create a ledger with the initial balances allocated at the start of the chain
+    pub fn new(allocations: &[(String, i64)]) -> Ledger {
+        let mut balances = HashMap::new();
+        for (account, amount) in allocations {
+            *balances.entry(account.clone()).or_insert(0) += amount;
+        }
+        Ledger {
+            balances,
+            nonces: HashMap::new(),
+        }
+    }
+
+    /// Get the balance of an account
+    pub fn balance(&self, account: &str) -> i64 {
+        self.balances.get(account).copied().unwrap_or(0)
+    }
+
+    /// Get the nonce the next transaction of an account must carry
+    pub fn nonce(&self, account: &str) -> u64 {
+        self.nonces.get(account).copied().unwrap_or(0)
+    }
+
+    /// Apply the transactions of a block to the ledger
+    /// Return false and leave the ledger untouched if any sender spends more than it holds, or skips or reuses a nonce
+    pub fn apply_block(&mut self, block: &Block) -> bool {
+        let mut balances = HashMap::<String, i64>::new();
+        let mut nonces = HashMap::<String, u64>::new();
+        for transaction in block.transactions.iter() {
+            if !transaction.is_coinbase() {
+                let nonce = nonces
+                    .entry(transaction.sender.clone())
+                    .or_insert_with(|| self.nonce(&transaction.sender));
+                if transaction.nonce != *nonce {
+                    return false;
+                }
+                *nonce += 1;
+                let sender = balances
+                    .entry(transaction.sender.clone())
+                    .or_insert_with(|| self.balance(&transaction.sender));
+                *sender -= spent(transaction);
+                if *sender < 0 {
+                    return false;
+                }
+            }
+            *balances
+                .entry(transaction.receiver.clone())
+                .or_insert_with(|| self.balance(&transaction.receiver)) +=
+                transaction.amount as i64;
+        }
+        self.balances.extend(balances);
+        self.nonces.extend(nonces);
+        true
+    }
+
+    /// Revert the transactions of a block that was previously applied to the ledger
+    pub fn revert_block(&mut self, block: &Block) {
+        for transaction in block.transactions.iter().rev() {
+            *self
+                .balances
+                .entry(transaction.receiver.clone())
+                .or_insert(0) -= transaction.amount as i64;
+            if !transaction.is_coinbase() {
+                *self.balances.entry(transaction.sender.clone()).or_insert(0) += spent(transaction);
+                self.nonces
+                    .insert(transaction.sender.clone(), transaction.nonce);
+            }
+        }
+    }
+
+    /// Check if the sender can afford the transaction on top of the pending transactions
+    pub fn can_afford<'a>(
+        &self,
+        pending: impl IntoIterator<Item = &'a Transaction>,
+        transaction: &Transaction,
+    ) -> bool {
+        let pending_spent: i64 = pending
+            .into_iter()
+            .filter(|tx| tx.sender == transaction.sender)
+            .map(spent)
+            .sum();
+        self.balance(&transaction.sender) - pending_spent >= spent(transaction)
+    }
+}
+
+/// The total amount debited from the sender of a transaction
+fn spent(transaction: &Transaction) -> i64 {
+    transaction.amount as i64 + transaction.fee as i64
+}
@@ -0,0 +1,132 @@
+//! ## Light client module
+//! A light client follows the blockchain of a peer node by only syncing the block headers
+//! The headers are enough to check the proof of work, the timestamps and the linkage of the chain without storing the transactions
+//! The light client checks that a transaction is confirmed with a merkle proof from the peer node
+use crate::models::blockchain::{block_work, check_header_timestamp_validity};
+use crate::models::chain_params::ChainParams;
+use crate::models::merkle::verify_transaction_proof;
+use crate::models::network::MAX_HEADERS;
+use crate::node::node_message_client::NodeMessageClient;
+use crate::node::{BlockHeader, GetHeadersRequest, GetTransactionProofRequest};
+use tonic::transport::Channel;
+use tonic::{Request, Status};
+
+pub struct LightClient {
+    pub headers: Vec<BlockHeader>,
+    pub params: ChainParams,
+    peer: NodeMessageClient<Channel>,
+}
+
+impl LightClient {
+    /// Connect to the peer node to follow
+    pub async fn connect(
+        peer: String,
+        params: ChainParams,
+    ) -> Result<LightClient, tonic::transport::Error> {
+        Ok(LightClient {
+            headers: Vec::new(),
+            params,
+            peer: NodeMessageClient::connect(peer).await?,
+        })
+    }
+
+    /// Sync the block headers from the peer node
+    /// Return true if the headers changed, the headers of the peer are only used if they have more accumulated work
+    pub async fn sync_headers(&mut self) -> Result<bool, Status> {
+        // start from the last known header, if the peer has a different one it switched to another branch
+        let mut start = self.headers.len().saturating_sub(1);
+        let mut headers = self.fetch_headers(start).await?;
+        if start > 0 && headers.first() != self.headers.get(start) {
+            start = 0;
+            headers = self.fetch_headers(start).await?;
+        }
+
+        let mut candidate = self.headers[..start].to_vec();
+        candidate.extend(headers);
+        if !self.check_headers(&candidate, start) {
+            println!("[Warning] Invalid block headers received from peer");
+            return Err(Status::invalid_argument(
+                "Invalid block headers received from peer",
+            ));
+        }
+        if header_work(&candidate) <= header_work(&self.headers) {
+            return Ok(false);
+        }
+        self.headers = candidate;
+        Ok(true)
+    }
+
+    /// Check that the transaction with the given hash is confirmed, with a merkle proof from the peer node and the synced headers
+    /// Return the number of confirmations, or `None` if the proof does not match the headers or is for another transaction
+    pub async fn verify_transaction(
+        &mut self,
+        transaction_hash: &str,
+    ) -> Result<Option<u32>, Status> {
+        let proof = self
+            .peer
+            .get_transaction_proof(Request::new(GetTransactionProofRequest {
+                transaction_hash: transaction_hash.to_string(),
+            }))
+            .await?
+            .into_inner();
+        Ok(verify_transaction_proof(
+            &self.headers,
+            &proof,
+            transaction_hash,
+        ))
+    }
+
+    /// Fetch all the headers of the peer from the given height
+    async fn fetch_headers(&mut self, start: usize) -> Result<Vec<BlockHeader>, Status> {
+        let mut headers = Vec::new();
+        loop {
+            let page = self
+                .peer
+                .get_headers(Request::new(GetHeadersRequest {
+                    start: (start + headers.len()) as u32,
+                }))
+                .await?
+                .into_inner()
+                .headers;
+            let done = page.len() < MAX_HEADERS;
+            headers.extend(page);
+            if done {
+                return Ok(headers);
+            }
+        }
+    }
+
+    /// Check the linkage, the proof of work, the timestamp and the difficulty of the headers from the given height
+    /// The first header must be the header of the genesis block of the network
+    fn check_headers(&self, headers: &[BlockHeader], start: usize) -> bool {
+        for i in start..headers.len() {
+            let header = &headers[i];
+            if i == 0 {
+                if *header != self.params.genesis.header() {
+                    return false;
+                }
+                continue;
+            }
+            if header.id as usize != i || header.prev_hash != headers[i - 1].hash {
+                return false;
+            }
+            if !header.check_header_validity() {
+                return false;
+            }
+            if !check_header_timestamp_validity(&headers[..i], header.timestamp) {
+                return false;
+            }
+            if header.difficulty != self.params.difficulty.next_header_difficulty(&headers[..i]) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// Get the accumulated proof of work of the headers
+fn header_work(headers: &[BlockHeader]) -> u128 {
+    headers.iter().fold(0u128, |work, header| {
+        work.saturating_add(block_work(header.difficulty))
+    })
+}
@@ -0,0 +1,413 @@
+//! ## Mempool module
+//! The mempool keeps the transactions waiting to be included in a block, indexed by id and by hash
+//! The transactions of every sender are kept in nonce order, the order they are included in blocks
+//! The nonces of a sender's pending transactions follow the nonce of the sender in the ledger without a gap
+//! The pool is limited in size, when it is full the transactions paying the least fee per byte are evicted first
+//! Transactions waiting longer than the maximum age are dropped before mining, so the ones that are never mined do not stay forever
+//! A sender can bump the fee of a pending transaction by sending a transaction with the same nonce and a higher fee, which replaces it
+//! Dropping a pending transaction also drops the later transactions of its sender, which cannot be mined without it
+use crate::models::blockchain::transaction_size;
+use crate::models::ledger::Ledger;
+use crate::node::Transaction;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::time::{Duration, Instant};
+
+/// The default maximum size of the pooled transactions, in encoded bytes
+pub const MAX_MEMPOOL_SIZE: usize = 10_000_000;
+/// The default time a transaction is kept in the pool while waiting to be mined
+pub const MAX_MEMPOOL_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+/// The minimum fee increment of a replacement transaction, so a transaction cannot be relayed over and over for nothing
+pub const MIN_REPLACEMENT_FEE_INCREMENT: i32 = 1;
+/// The minimum fee increment of a replacement transaction, in percent of the fee of the replaced transaction
+pub const REPLACEMENT_FEE_INCREMENT_PERCENT: i32 = 10;
+
+/// The reason a transaction is not added to the pool
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RejectReason {
+    /// The transaction is already in the pool
+    Duplicate,
+    /// The hash or the signature of the transaction is invalid
+    Invalid,
+    /// The sender cannot afford the transaction on top of its pending transactions
+    InsufficientBalance,
+    /// The pool is full and the transaction pays too little fee per byte to evict other transactions
+    PoolFull,
+    /// The transaction replaces a pending transaction without paying the replacement fee
+    ReplacementFeeTooLow,
+    /// The nonce of the transaction was already used by a transaction in the chain
+    NonceTooLow,
+    /// The nonce of the transaction does not follow the nonce of the last pending transaction of the sender
+    NonceGap,
+}
+
+impl fmt::Display for RejectReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let reason = match self {
+            RejectReason::Duplicate => "Transaction is already in the pool",
+            RejectReason::Invalid => "Invalid transaction hash or signature",
+            RejectReason::InsufficientBalance => "Transaction exceeds the sender balance",
+            RejectReason::PoolFull => "Transaction pool is full and the fee is too low",
+            RejectReason::ReplacementFeeTooLow => {
+                "Replacement transaction does not pay enough more fee than the pending one"
+            }
+            RejectReason::NonceTooLow => "Transaction nonce was already used",
+            RejectReason::NonceGap => "Transaction nonce is ahead of the next nonce of the sender",
+        };
+        write!(f, "{}", reason)
+    }
+}
+
+struct Entry {
+    transaction: Transaction,
+    size: usize,
+    added: Instant,
+}
+
+impl Entry {
+    /// Compare the fee per byte of two entries
+    fn cmp_fee_rate(&self, other: &Entry) -> std::cmp::Ordering {
+        (self.transaction.fee as i64 * other.size as i64)
+            .cmp(&(other.transaction.fee as i64 * self.size as i64))
+    }
+}
+
+pub struct Mempool {
+    pub max_size: usize,
+    pub max_age: Duration,
+    /// The pooled transactions, keyed by their position in the pool
+    entries: BTreeMap<i64, Entry>,
+    by_id: HashMap<String, i64>,
+    by_hash: HashMap<String, i64>,
+    /// The positions of the transactions of every sender, keyed by nonce
+    by_sender: HashMap<String, BTreeMap<u64, i64>>,
+    size: usize,
+    /// The positions given to the next transactions added at the front and at the back of the pool
+    front: i64,
+    back: i64,
+}
+
+impl Default for Mempool {
+    fn default() -> Mempool {
+        Mempool::new(MAX_MEMPOOL_SIZE, MAX_MEMPOOL_AGE)
+    }
+}
+
+impl Mempool {
+    pub fn new(max_size: usize, max_age: Duration) -> Mempool {
+        Mempool {
+            max_size,
+            max_age,
+            entries: BTreeMap::new(),
+            by_id: HashMap::new(),
+            by_hash: HashMap::new(),
+            by_sender: HashMap::new(),
+            size: 0,
+            front: -1,
+            back: 0,
+        }
+    }
+
+    /// Get the number of transactions in the pool
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Check if the pool is empty
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Get the encoded size of the pooled transactions, in bytes
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Check if the transaction is in the pool
+    pub fn contains(&self, transaction: &Transaction) -> bool {
+        self.by_hash
+            .get(&transaction.hash)
+            .is_some_and(|position| self.entries[position].transaction == *transaction)
+    }
+
+    /// Get the pooled transaction with the given id
+    pub fn get(&self, id: &str) -> Option<&Transaction> {
+        self.by_id
+            .get(id)
+            .map(|position| &self.entries[position].transaction)
+    }
+
+    /// Iterate over the pooled transactions, in the order they were added
+    pub fn iter(&self) -> impl Iterator<Item = &Transaction> {
+        self.entries.values().map(|entry| &entry.transaction)
+    }
+
+    /// Get a copy of the pooled transactions, in the order they were added
+    pub fn to_vec(&self) -> Vec<Transaction> {
+        self.iter().cloned().collect()
+    }
+
+    /// Get the pooled transactions of a sender, in nonce order
+    pub fn sender_transactions(&self, sender: &str) -> Vec<&Transaction> {
+        self.by_sender
+            .get(sender)
+            .into_iter()
+            .flatten()
+            .map(|(_, position)| &self.entries[position].transaction)
+            .collect()
+    }
+
+    /// Get the nonce of the next transaction of a sender, after its pending transactions
+    pub fn next_nonce(&self, sender: &str, ledger: &Ledger) -> u64 {
+        self.by_sender
+            .get(sender)
+            .and_then(|nonces| nonces.keys().next_back())
+            .map_or(ledger.nonce(sender), |nonce| nonce + 1)
+    }
+
+    /// Add a transaction to the pool if the sender can afford it on top of its pending transactions
+    /// The transaction must carry the next nonce of its sender, or the nonce of a pending transaction of the sender
+    /// A transaction with the nonce of a pending transaction replaces it, if it pays at least the replacement fee
+    /// When the pool is full, the transactions paying less fee per byte are evicted to make room
+    /// Return the replaced transaction
+    pub fn insert(
+        &mut self,
+        transaction: Transaction,
+        ledger: &Ledger,
+    ) -> Result<Option<Transaction>, RejectReason> {
+        if self.by_hash.contains_key(&transaction.hash) {
+            return Err(RejectReason::Duplicate);
+        }
+        if transaction.nonce < ledger.nonce(&transaction.sender) {
+            return Err(RejectReason::NonceTooLow);
+        }
+        let replaced = match self.position(&transaction.sender, transaction.nonce) {
+            Some(position) => {
+                let pooled = &self.entries[&position].transaction;
+                if transaction.fee < replacement_fee(pooled.fee) {
+                    return Err(RejectReason::ReplacementFeeTooLow);
+                }
+                Some(position)
+            }
+            None if transaction.nonce != self.next_nonce(&transaction.sender, ledger) => {
+                return Err(RejectReason::NonceGap);
+            }
+            None => None,
+        };
+        if self
+            .by_id
+            .get(&transaction.id)
+            .is_some_and(|&position| Some(position) != replaced)
+        {
+            return Err(RejectReason::Duplicate);
+        }
+        let pending = self
+            .sender_transactions(&transaction.sender)
+            .into_iter()
+            .filter(|pooled| pooled.nonce != transaction.nonce);
+        if !ledger.can_afford(pending, &transaction) {
+            return Err(RejectReason::InsufficientBalance);
+        }
+
+        let entry = Entry {
+            size: transaction_size(&transaction),
+            transaction,
+            added: Instant::now(),
+        };
+        let size = self.size - replaced.map_or(0, |position| self.entries[&position].size);
+        if size + entry.size > self.max_size {
+            // only the last pending transaction of another sender can be evicted, or a gap would open in its nonces
+            let mut queues: Vec<Vec<(i64, &Entry)>> = self
+                .by_sender
+                .iter()
+                .filter(|(sender, _)| **sender != entry.transaction.sender)
+                .map(|(_, nonces)| {
+                    nonces
+                        .values()
+                        .map(|position| (*position, &self.entries[position]))
+                        .collect()
+                })
+                .collect();
+            let mut freed = 0;
+            let mut evicted = Vec::new();
+            while size - freed + entry.size > self.max_size {
+                // only evict the transactions paying less than the new one, or the pool would churn at the same fee
+                let lowest = queues
+                    .iter_mut()
+                    .filter(|queue| {
+                        queue
+                            .last()
+                            .is_some_and(|(_, pooled)| pooled.cmp_fee_rate(&entry).is_lt())
+                    })
+                    .min_by(|a, b| a.last().unwrap().1.cmp_fee_rate(b.last().unwrap().1));
+                let Some((position, pooled)) = lowest.and_then(|queue| queue.pop()) else {
+                    return Err(RejectReason::PoolFull);
+                };
+                freed += pooled.size;
+                evicted.push(position);
+            }
+            for position in evicted {
+                self.remove_at(position);
+            }
+        }
+
+        // the replacement takes the place of the replaced transaction in the pool
+        let (position, replaced) = match replaced {
+            Some(position) => (position, Some(self.remove_at(position).transaction)),
+            None => {
+                self.back += 1;
+                (self.back - 1, None)
+            }
+        };
+        self.insert_at(position, entry);
+        Ok(replaced)
+    }
+
+    /// Return transactions to the front of the pool, ahead of the pooled transactions
+    /// The transactions are not checked against the balances and the nonces, the caller prunes the pool afterwards
+    pub fn reinsert(&mut self, transactions: Vec<Transaction>) {
+        for transaction in transactions.into_iter().rev() {
+            if self.by_id.contains_key(&transaction.id)
+                || self.by_hash.contains_key(&transaction.hash)
+                || self
+                    .position(&transaction.sender, transaction.nonce)
+                    .is_some()
+            {
+                continue;
+            }
+            let position = self.front;
+            self.front -= 1;
+            self.insert_at(
+                position,
+                Entry {
+                    size: transaction_size(&transaction),
+                    transaction,
+                    added: Instant::now(),
+                },
+            );
+        }
+        // the pool may be over its size now, drop the last transactions of the senders paying the least
+        while self.size > self.max_size {
+            let lowest = self
+                .by_sender
+                .values()
+                .filter_map(|nonces| nonces.values().next_back())
+                .min_by(|a, b| self.entries[a].cmp_fee_rate(&self.entries[b]))
+                .copied();
+            match lowest {
+                Some(position) => self.remove_at(position),
+                None => break,
+            };
+        }
+    }
+
+    /// Remove the transaction with the given hash from the pool, with the later transactions of its sender
+    pub fn remove(&mut self, hash: &str) -> Option<Transaction> {
+        let position = *self.by_hash.get(hash)?;
+        let transaction = self.entries[&position].transaction.clone();
+        self.remove_from(&transaction.sender, transaction.nonce);
+        Some(transaction)
+    }
+
+    /// Remove the transactions that are included in a block, and the pending transactions using the same nonces
+    pub fn remove_included(&mut self, transactions: &[Transaction]) {
+        for transaction in transactions {
+            if let Some(position) = self.position(&transaction.sender, transaction.nonce) {
+                self.remove_at(position);
+            }
+        }
+    }
+
+    /// Drop the transactions that waited in the pool longer than the maximum age, with the later transactions of their senders
+    /// Return the number of dropped transactions
+    pub fn expire(&mut self) -> usize {
+        let expired: Vec<(String, u64)> = self
+            .entries
+            .values()
+            .filter(|entry| entry.added.elapsed() > self.max_age)
+            .map(|entry| (entry.transaction.sender.clone(), entry.transaction.nonce))
+            .collect();
+        expired
+            .iter()
+            .map(|(sender, nonce)| self.remove_from(sender, *nonce))
+            .sum()
+    }
+
+    /// Drop the transactions whose nonce is used in the chain, or that can no longer be afforded with the current balances
+    /// The transactions of a sender are checked in nonce order, so the later ones are dropped first, and a gap drops the rest
+    pub fn prune(&mut self, ledger: &Ledger) {
+        let mut dropped = Vec::new();
+        for (sender, nonces) in &self.by_sender {
+            let mut next = ledger.nonce(sender);
+            let mut pending = Vec::new();
+            for (&nonce, position) in nonces {
+                let transaction = &self.entries[position].transaction;
+                if nonce == next && ledger.can_afford(pending.iter().copied(), transaction) {
+                    pending.push(transaction);
+                    next += 1;
+                } else {
+                    dropped.push(*position);
+                }
+            }
+        }
+        for position in dropped {
+            self.remove_at(position);
+        }
+    }
+
+    /// Get the position of the pending transaction of a sender with the given nonce
+    fn position(&self, sender: &str, nonce: u64) -> Option<i64> {
+        self.by_sender
+            .get(sender)
+            .and_then(|nonces| nonces.get(&nonce))
+            .copied()
+    }
+
+    /// Remove the pending transactions of a sender from the given nonce on
+    /// Return the number of removed transactions
+    fn remove_from(&mut self, sender: &str, nonce: u64) -> usize {
+        let positions: Vec<i64> = self
+            .by_sender
+            .get(sender)
+            .into_iter()
+            .flat_map(|nonces| nonces.range(nonce..).map(|(_, position)| *position))
+            .collect();
+        for position in &positions {
+            self.remove_at(*position);
+        }
+        positions.len()
+    }
+
+    fn insert_at(&mut self, position: i64, entry: Entry) {
+        let transaction = &entry.transaction;
+        self.by_id.insert(transaction.id.clone(), position);
+        self.by_hash.insert(transaction.hash.clone(), position);
+        self.by_sender
+            .entry(transaction.sender.clone())
+            .or_default()
+            .insert(transaction.nonce, position);
+        self.size += entry.size;
+        self.entries.insert(position, entry);
+    }
+
+    fn remove_at(&mut self, position: i64) -> Entry {
+        let entry = self.entries.remove(&position).unwrap();
+        let transaction = &entry.transaction;
+        self.by_id.remove(&transaction.id);
+        self.by_hash.remove(&transaction.hash);
+        if let Some(positions) = self.by_sender.get_mut(&transaction.sender) {
+            positions.remove(&transaction.nonce);
+            if positions.is_empty() {
+                self.by_sender.remove(&transaction.sender);
+            }
+        }
+        self.size -= entry.size;
+        entry
+    }
+}
+
+/// Get the minimum fee of a transaction that replaces a pending transaction paying the given fee
+pub fn replacement_fee(fee: i32) -> i32 {
+    let increment = (fee as i64 * REPLACEMENT_FEE_INCREMENT_PERCENT as i64 / 100) as i32;
+    fee.saturating_add(increment.max(MIN_REPLACEMENT_FEE_INCREMENT))
+}
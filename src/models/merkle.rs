@@ -0,0 +1,98 @@
+//! ## Merkle module
+//! This module contains the Merkle tree built over the transaction hashes of a block
+//! The root of the tree is stored in the block header, so the header hash commits to every transaction without including them
+//! Each parent node is the SHA256 hash of its two children, and the last node of a level is paired with itself when the level has an odd size
+//! A merkle branch proves that a transaction is part of a block to a client that only knows the block headers
+use crate::models::blockchain::{check_header_chain, sha_hash};
+use crate::node::{BlockHeader, GetTransactionProofResponse};
+
+/// Compute the Merkle root of the given transaction hashes
+pub fn merkle_root(hashes: &[String]) -> String {
+    if hashes.is_empty() {
+        return "".to_string();
+    }
+    let mut level = hashes.to_vec();
+    while level.len() > 1 {
+        level = next_level(&level);
+    }
+    level.remove(0)
+}
+
+/// Hash every pair of nodes of a level to get the level above
+fn next_level(level: &[String]) -> Vec<String> {
+    level
+        .chunks(2)
+        .map(|pair| {
+            let left = &pair[0];
+            let right = pair.get(1).unwrap_or(left);
+            sha_hash(&format!("{}{}", left, right))
+        })
+        .collect()
+}
+
+/// Get the merkle branch of the hash at the given index, the sibling hashes from the leaf up to the root
+pub fn merkle_branch(hashes: &[String], mut index: usize) -> Vec<String> {
+    let mut branch = Vec::new();
+    let mut level = hashes.to_vec();
+    while level.len() > 1 {
+        let sibling = (index ^ 1).min(level.len() - 1);
+        branch.push(level[sibling].clone());
+        level = next_level(&level);
+        index /= 2;
+    }
+    branch
+}
+
+/// Check if the hash at the given index and its merkle branch lead to the merkle root
+/// The index must fit in the depth of the branch, so a proof holds for a single index
+pub fn verify_merkle_branch(hash: &str, mut index: usize, branch: &[String], root: &str) -> bool {
+    if index.checked_shr(branch.len() as u32).unwrap_or(0) != 0 {
+        return false;
+    }
+    let mut current = hash.to_string();
+    for sibling in branch {
+        current = if index.is_multiple_of(2) {
+            sha_hash(&format!("{}{}", current, sibling))
+        } else {
+            sha_hash(&format!("{}{}", sibling, current))
+        };
+        index /= 2;
+    }
+    current == root
+}
+
+/// Verify a transaction proof against a chain of block headers known by the client
+/// Return the number of confirmations of the transaction, or `None` if the proof is not valid for the header chain or is for another transaction
+pub fn verify_transaction_proof(
+    headers: &[BlockHeader],
+    proof: &GetTransactionProofResponse,
+    expected_hash: &str,
+) -> Option<u32> {
+    let transaction = proof.transaction.as_ref()?;
+    let header = proof.header.as_ref()?;
+    if transaction.hash != expected_hash {
+        return None;
+    }
+    if !check_header_chain(headers) {
+        return None;
+    }
+
+    // the header of the proof must be part of the header chain
+    let known_header = headers.get(usize::try_from(header.id).ok()?)?;
+    if known_header != header {
+        return None;
+    }
+
+    // the transaction must be the one committed at the index of the merkle tree
+    if transaction.hash != transaction.compute_hash()
+        || !verify_merkle_branch(
+            &transaction.hash,
+            proof.index as usize,
+            &proof.branch,
+            &header.merkle_root,
+        )
+    {
+        return None;
+    }
+    Some((headers.len() - header.id as usize) as u32)
+}
@@ -0,0 +1,289 @@
+//! ## Miner module
+//! A mining backend searches the nonce space for a nonce that gives the block header the required proof of work
+//! The search runs on its own threads, off the async runtime, so mining does not slow down the gRPC handling
+//! The CPU miner splits the nonce space across a number of threads, the first thread to find a valid nonce wins
+//! With the `cuda` feature the nodes mine on the GPU instead, see the cudalib module
+//! The miner keeps statistics of the hashes attempted and the blocks mined, so operators can see how fast the node mines
+use crate::models::blockchain::check_hash_validity;
+use crate::node::{Block, GetMiningInfoResponse};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc::Receiver;
+use tonic::Status;
+
+/// The windows the hashrate is averaged over
+pub const HASHRATE_WINDOWS: [Duration; 3] = [
+    Duration::from_secs(60),
+    Duration::from_secs(5 * 60),
+    Duration::from_secs(15 * 60),
+];
+/// How many nonces a CPU mining thread tries before adding them to the hash count
+const HASH_COUNT_BATCH: u64 = 1024;
+
+pub trait MiningBackend: Send + Sync {
+    /// Search the nonce space for a nonce that gives the block header its difficulty
+    /// Return the winning nonce and the header hash, or None if the search was cancelled or no nonce works
+    /// This call blocks until the search ends, and returns soon after `cancel` is set
+    /// The number of hashes computed is added to `hashes` while searching
+    fn mine(&self, block: &Block, cancel: &AtomicBool, hashes: &AtomicU64)
+        -> Option<(i32, String)>;
+}
+
+/// A miner that splits the nonce space across a number of OS threads
+/// Thread `i` of `n` tries the nonces `i`, `i + n`, `i + 2n` and so on
+#[derive(Clone, Debug)]
+pub struct CpuMiner {
+    pub threads: usize,
+}
+
+impl Default for CpuMiner {
+    fn default() -> CpuMiner {
+        CpuMiner::new(default_threads())
+    }
+}
+
+impl CpuMiner {
+    pub fn new(threads: usize) -> CpuMiner {
+        CpuMiner {
+            threads: threads.max(1),
+        }
+    }
+}
+
+impl MiningBackend for CpuMiner {
+    fn mine(
+        &self,
+        block: &Block,
+        cancel: &AtomicBool,
+        hashes: &AtomicU64,
+    ) -> Option<(i32, String)> {
+        let threads = self.threads.max(1) as i64;
+        let found = Mutex::new(None);
+        let done = AtomicBool::new(false);
+        thread::scope(|scope| {
+            for first in 0..threads {
+                let (found, done) = (&found, &done);
+                scope.spawn(move || {
+                    let mut nonce = first;
+                    let mut count = 0;
+                    while nonce <= i32::MAX as i64 {
+                        if done.load(Ordering::Relaxed) || cancel.load(Ordering::Relaxed) {
+                            break;
+                        }
+                        let hash = block.compute_hash(nonce as i32);
+                        count += 1;
+                        if check_hash_validity(&hash, block.difficulty) {
+                            found.lock().unwrap().get_or_insert((nonce as i32, hash));
+                            done.store(true, Ordering::Relaxed);
+                            break;
+                        }
+                        if count == HASH_COUNT_BATCH {
+                            hashes.fetch_add(count, Ordering::Relaxed);
+                            count = 0;
+                        }
+                        nonce += threads;
+                    }
+                    hashes.fetch_add(count, Ordering::Relaxed);
+                });
+            }
+        });
+        found.into_inner().unwrap()
+    }
+}
+
+/// Get the mining backend of the build, the GPU miner with the `cuda` feature, which falls back to a CPU miner with the given number of threads
+#[cfg(feature = "cuda")]
+pub fn default_backend(threads: usize) -> Arc<dyn MiningBackend> {
+    Arc::new(crate::models::cudalib::CudaMiner::new(CpuMiner::new(
+        threads,
+    )))
+}
+
+/// Get the mining backend of the build, a CPU miner with the given number of threads without the `cuda` feature
+#[cfg(not(feature = "cuda"))]
+pub fn default_backend(threads: usize) -> Arc<dyn MiningBackend> {
+    Arc::new(CpuMiner::new(threads))
+}
+
+/// Get the default number of mining threads, one per available CPU
+pub fn default_threads() -> usize {
+    thread::available_parallelism()
+        .map(|threads| threads.get())
+        .unwrap_or(1)
+}
+
+/// The statistics of the mining of a node
+pub struct MiningStats {
+    hashes: AtomicU64,
+    blocks_mined: AtomicU64,
+    interrupted: AtomicU64,
+    mining: AtomicBool,
+    /// The time of the last block mined, or the time the miner started
+    last_block: Mutex<Instant>,
+    /// The hash count over time, from the oldest to the newest sample within the largest window
+    samples: Mutex<VecDeque<(Instant, u64)>>,
+}
+
+impl Default for MiningStats {
+    fn default() -> MiningStats {
+        let now = Instant::now();
+        MiningStats {
+            hashes: AtomicU64::new(0),
+            blocks_mined: AtomicU64::new(0),
+            interrupted: AtomicU64::new(0),
+            mining: AtomicBool::new(false),
+            last_block: Mutex::new(now),
+            samples: Mutex::new(VecDeque::from([(now, 0)])),
+        }
+    }
+}
+
+impl MiningStats {
+    /// Get the number of hashes attempted
+    pub fn hashes(&self) -> u64 {
+        self.hashes.load(Ordering::Relaxed)
+    }
+
+    /// Get the number of blocks mined
+    pub fn blocks_mined(&self) -> u64 {
+        self.blocks_mined.load(Ordering::Relaxed)
+    }
+
+    /// Get the number of mining attempts stopped before finding a block
+    pub fn interrupted(&self) -> u64 {
+        self.interrupted.load(Ordering::Relaxed)
+    }
+
+    /// Check if a block is being mined
+    pub fn is_mining(&self) -> bool {
+        self.mining.load(Ordering::Relaxed)
+    }
+
+    /// Get the time since the last block mined, or since the miner started if no block was mined
+    pub fn since_last_block(&self) -> Duration {
+        self.last_block.lock().unwrap().elapsed()
+    }
+
+    /// Record the current hash count, the hashrate is computed from these samples
+    pub fn record_sample(&self) {
+        let now = Instant::now();
+        let mut samples = self.samples.lock().unwrap();
+        samples.push_back((now, self.hashes()));
+        let max_window = HASHRATE_WINDOWS[HASHRATE_WINDOWS.len() - 1];
+        while samples
+            .front()
+            .is_some_and(|(time, _)| now.duration_since(*time) > max_window)
+        {
+            samples.pop_front();
+        }
+    }
+
+    /// Get the average number of hashes per second over the window
+    /// The average starts from the oldest sample within the window, so it covers less time while the node is starting
+    pub fn hashrate(&self, window: Duration) -> f64 {
+        let now = Instant::now();
+        let hashes = self.hashes();
+        let samples = self.samples.lock().unwrap();
+        match samples
+            .iter()
+            .find(|(time, _)| now.duration_since(*time) <= window)
+        {
+            Some((time, count)) => {
+                let seconds = now.duration_since(*time).as_secs_f64();
+                if seconds > 0.0 {
+                    hashes.saturating_sub(*count) as f64 / seconds
+                } else {
+                    0.0
+                }
+            }
+            None => 0.0,
+        }
+    }
+
+    /// Get the statistics in the format of the GetMiningInfo RPC
+    pub fn info(&self) -> GetMiningInfoResponse {
+        GetMiningInfoResponse {
+            hashes: self.hashes(),
+            hashrate1m: self.hashrate(HASHRATE_WINDOWS[0]),
+            hashrate5m: self.hashrate(HASHRATE_WINDOWS[1]),
+            hashrate15m: self.hashrate(HASHRATE_WINDOWS[2]),
+            blocks_mined: self.blocks_mined(),
+            interrupted: self.interrupted(),
+            seconds_since_last_block: self.since_last_block().as_secs(),
+            mining: self.is_mining(),
+        }
+    }
+}
+
+/// A mining backend with the statistics of its mining
+#[derive(Clone)]
+pub struct Miner {
+    backend: Arc<dyn MiningBackend>,
+    pub stats: Arc<MiningStats>,
+}
+
+impl Miner {
+    pub fn new(backend: Arc<dyn MiningBackend>) -> Miner {
+        Miner {
+            backend,
+            stats: Arc::new(MiningStats::default()),
+        }
+    }
+
+    /// Mine the block with the backend, the block must have everything but its nonce and hash
+    /// The mining is cancelled when a signal is received, and an error is returned
+    pub async fn mine_block(
+        &self,
+        mut block: Block,
+        rx: &mut Receiver<bool>,
+    ) -> Result<Block, Status> {
+        println!("[INFO] Mining new block");
+        let started = Instant::now();
+        let first_hash = self.stats.hashes();
+        self.stats.mining.store(true, Ordering::Relaxed);
+        let cancel = Arc::new(AtomicBool::new(false));
+        let mut search = tokio::task::spawn_blocking({
+            let backend = self.backend.clone();
+            let stats = self.stats.clone();
+            let block = block.clone();
+            let cancel = cancel.clone();
+            move || backend.mine(&block, &cancel, &stats.hashes)
+        });
+
+        let result = tokio::select! {
+            result = &mut search => result,
+            Some(_) = rx.recv() => {
+                // wait for the threads to stop, so they do not keep mining in the background
+                cancel.store(true, Ordering::Relaxed);
+                let _ = search.await;
+                self.stats.mining.store(false, Ordering::Relaxed);
+                self.stats.interrupted.fetch_add(1, Ordering::Relaxed);
+                println!(
+                    "[INFO] Mining interrupted after {} hashes",
+                    self.stats.hashes() - first_hash
+                );
+                return Err(Status::cancelled("Mining stopped"));
+            }
+        };
+        self.stats.mining.store(false, Ordering::Relaxed);
+        let (nonce, hash) = result
+            .map_err(|e| Status::internal(format!("Mining failed: {}", e)))?
+            .ok_or_else(|| Status::resource_exhausted("No nonce gives the block its difficulty"))?;
+        block.nonce = nonce;
+        block.hash = hash;
+        self.stats.blocks_mined.fetch_add(1, Ordering::Relaxed);
+        *self.stats.last_block.lock().unwrap() = Instant::now();
+
+        println!(
+            "[INFO] New block mined: id = {:?}, hash = {:?}, {} hashes in {:.1}s",
+            block.id,
+            block.hash,
+            self.stats.hashes() - first_hash,
+            started.elapsed().as_secs_f64()
+        );
+        Ok(block)
+    }
+}
@@ -1,140 +1,407 @@
 //! This module contains the implementation of the Network struct
 //! This is for all the gRPC service implementations
+use crate::models::blockchain::{
+    block_template, chain_work, check_timestamp_validity, next_timestamp, Blockchain,
+};
+use crate::models::broadcast::ChainSnapshot;
+use crate::models::mempool::RejectReason;
+use crate::models::merkle::merkle_branch;
 use crate::models::node::Node;
 
 use crate::node::{
-    node_message_client::NodeMessageClient, node_message_server::NodeMessage, GetBlockchainRequest,
-    GetBlockchainResponse, JoinNetworkRequest, JoinNetworkResponse, UpdateBlockchainRequest,
-    UpdateBlockchainResponse, UpdateTransactionRequest, UpdateTransactionResponse,
+    node_message_server::NodeMessage, GetBlockchainRequest, GetBlockchainResponse,
+    JoinNetworkRequest, JoinNetworkResponse, UpdateBlockchainRequest, UpdateBlockchainResponse,
+    UpdateTransactionRequest, UpdateTransactionResponse,
 };
+use crate::node::{Block, GetBlocksRequest, GetBlocksResponse, NodeInfo};
 use crate::node::{
-    GenerateTransactionRequest, GenerateTransactionResponse, GetPeerListRequest,
-    GetPeerListResponse, GetTransactionListRequest, GetTransactionListResponse, Transaction,
+    GenerateBlocksRequest, GenerateBlocksResponse, GenerateTransactionRequest,
+    GenerateTransactionResponse, GetAccountRequest, GetAccountResponse, GetHeadersRequest,
+    GetHeadersResponse, GetMiningInfoRequest, GetMiningInfoResponse, GetPeerListRequest,
+    GetPeerListResponse, GetTransactionListRequest, GetTransactionListResponse,
+    GetTransactionProofRequest, GetTransactionProofResponse, RejectedTransaction, Transaction,
 };
 use openssl::{hash::MessageDigest, pkey::PKey, rsa::Rsa, sign::Signer};
+use std::net::{IpAddr, SocketAddr};
 use std::sync::Arc;
 use std::time::SystemTime;
-use tokio::sync::mpsc::Sender;
+use tokio::sync::mpsc::{self, error::TrySendError, Sender};
 use tonic::{Request, Response, Status};
 
+/// The maximum number of blocks returned by one GetBlocks request
+pub const MAX_BLOCKS: usize = 500;
+/// The maximum number of headers returned by one GetHeaders request
+pub const MAX_HEADERS: usize = 2000;
+/// The maximum number of blocks an orphan block can be ahead of the blockchain
+pub const MAX_ORPHAN_GAP: usize = 4 * MAX_BLOCKS;
+
+#[derive(Clone)]
 pub struct Network {
     pub node: Arc<Node>,
     pub tx: Sender<bool>,
 }
 
-/// Implement the NodeMessage trait for the BlockchainNetwork struct
-/// This will allow the BlockchainNetwork struct to be used as a gRPC service
-#[tonic::async_trait]
-impl NodeMessage for Network {
-    async fn join_network(
+impl Network {
+    /// Validate the received blocks and replace the current blockchain if they make a chain with more accumulated work
+    /// Blocks received before their previous blocks are kept in the orphan pool, and the missing blocks are requested from the sender
+    async fn receive_blocks(
         &self,
-        request: Request<JoinNetworkRequest>,
-    ) -> Result<Response<JoinNetworkResponse>, Status> {
-        let mut peers = self.node.peers.lock().await;
-        let req_node = request.into_inner().node.unwrap();
-
-        peers.push(req_node.clone());
-        let current_bc = self.node.blockchain.lock().await;
-
-        let reply = JoinNetworkResponse {
-            nodes: (*peers).clone(),
-            chain: current_bc.chain.clone(),
-            transactions: current_bc.transactions.clone(),
-        };
-        println!("[INFO] New node joined the network: {:?}", req_node.port);
-        Ok(Response::new(reply))
-    }
-
-    /// Receive the blockchain from another node, if the received blockchain is longer than the current blockchain, then replace the current blockchain with the received blockchain
-    async fn update_blockchain(
-        &self,
-        request: Request<UpdateBlockchainRequest>,
-    ) -> Result<Response<UpdateBlockchainResponse>, Status> {
-        let req = request.into_inner();
-        let blocks = req.blocks;
+        blocks: Vec<Block>,
+        sender: Option<NodeInfo>,
+    ) -> Result<UpdateBlockchainResponse, Status> {
         let current_bc = self.node.blockchain.lock().await;
         let mut chain = current_bc.chain.clone();
+        let total_work = current_bc.total_work();
+        let params = current_bc.params.clone();
+        // the fork of the chain that ends with the parent of the received blocks, on the active chain or on a side branch
+        let parent = blocks.first().and_then(|block| {
+            let height = usize::try_from(block.id).ok()?.checked_sub(1)?;
+            current_bc.fork(&block.prev_hash, height)
+        });
         drop(current_bc);
 
         if blocks.is_empty() {
             println!("[Warning] Received blocks is empty");
-            return Ok(Response::new(UpdateBlockchainResponse {
+            return Ok(UpdateBlockchainResponse {
                 success: false,
                 chain_length: chain.len() as u32,
-            }));
+                total_work: total_work.to_string(),
+            });
+        }
+        if blocks.iter().any(|block| block.id < 0) {
+            println!("[Warning] Received block with a negative id");
+            return Err(Status::invalid_argument(
+                "Received block with a negative id",
+            ));
         }
-        if blocks[0].id as usize > chain.len() {
+        if parent.is_none() && blocks[0].id as usize > chain.len() {
+            // the missing blocks are only requested for a small gap, so a single cheap block cannot make the node download without limit
+            if blocks
+                .iter()
+                .any(|block| block.id as usize > chain.len() + MAX_ORPHAN_GAP)
+            {
+                println!("[Warning] Orphan blocks are too far ahead of the blockchain");
+                return Err(Status::invalid_argument(
+                    "Orphan blocks are too far ahead of the blockchain",
+                ));
+            }
             // should return false to get updated blockchain
             println!("[Warning] Need previous blocks to update blockchain, current blockchain length: {}",
                      chain.len());
-            return Ok(Response::new(UpdateBlockchainResponse {
+            self.add_orphans(blocks, sender).await;
+            Ok(UpdateBlockchainResponse {
                 success: false,
                 chain_length: chain.len() as u32,
-            }));
+                total_work: total_work.to_string(),
+            })
         } else {
-            // check if the received blockchain is longer than the current blockchain
-            if (blocks.last().unwrap().id as usize) < chain.len() {
-                println!("[Warning] Received blockchain is shorter than current blockchain",);
-                return Err(Status::invalid_argument(
-                    "Received blockchain is shorter than current blockchain",
-                ));
+            // follow the parent of the received blocks, or truncate the current blockchain if the parent is unknown
+            let first_id = blocks[0].id;
+            match parent {
+                Some((fork, branch)) => {
+                    chain.truncate(fork);
+                    chain.extend(branch);
+                }
+                None => chain.truncate(first_id as usize),
             }
-
-            // truncate the current blockchain and add the received blocks
-            chain.truncate(blocks[0].id as usize);
             chain.extend(blocks.clone());
 
             let mut prev_hash = String::new();
-            if blocks[0].id != 0 {
-                prev_hash = chain[blocks[0].id as usize - 1].hash.clone();
+            if first_id != 0 {
+                prev_hash = chain[first_id as usize - 1].hash.clone();
             }
-            let mut encluded_transactions = Vec::<Transaction>::new();
             // check if the received blockchain is valid
-            for block in blocks {
+            for (i, block) in blocks.iter().enumerate() {
+                if block.id != first_id + i as i32 {
+                    println!("[Warning] Block ids are not consecutive in received blockchain");
+                    return Err(Status::invalid_argument(
+                        "Block ids are not consecutive in received blockchain",
+                    ));
+                }
+
                 if block.prev_hash != prev_hash {
                     println!("[Warning] Previous hash does not match in block ");
-                    return Ok(Response::new(UpdateBlockchainResponse {
+                    return Ok(UpdateBlockchainResponse {
                         success: false,
                         chain_length: 0,
-                    }));
+                        total_work: total_work.to_string(),
+                    });
                 }
 
                 prev_hash = block.hash.clone();
 
-                if !block.check_block_validity() {
+                // the genesis block is hard-coded, a chain that starts from another one belongs to another network
+                if block.id == 0 {
+                    if *block != params.genesis {
+                        println!("[Warning] Received blockchain starts from another genesis block");
+                        return Err(Status::invalid_argument(
+                            "Received blockchain starts from another genesis block",
+                        ));
+                    }
+                    continue;
+                }
+
+                if !block.check_block_validity(&params) {
                     println!("[Warning] Invalid block in received blockchain");
                     return Err(Status::invalid_argument(
                         "Invalid block in received blockchain",
                     ));
                 }
 
-                encluded_transactions.extend(block.transactions.clone());
+                if !check_timestamp_validity(&chain[..block.id as usize], block.timestamp) {
+                    println!("[Warning] Invalid block timestamp in received blockchain");
+                    return Err(Status::invalid_argument(
+                        "Invalid block timestamp in received blockchain",
+                    ));
+                }
+
+                // the difficulty is derived from the blocks before, so peers cannot pick an easier one
+                if block.difficulty
+                    != params
+                        .difficulty
+                        .next_difficulty(&chain[..block.id as usize])
+                {
+                    println!("[Warning] Block difficulty does not follow the retarget schedule");
+                    return Err(Status::invalid_argument(
+                        "Block difficulty does not follow the retarget schedule",
+                    ));
+                }
             }
 
-            // if the received blockchain is valid, then update the current blockchain
+            // check if the received blockchain has more work than the current blockchain, the peer can stop sending when it has less
             let mut current_bc = self.node.blockchain.lock().await;
-            current_bc.chain = chain;
-
-            // remove the transactions that are included in the new blockchain
-            current_bc
-                .transactions
-                .retain(|tx| !encluded_transactions.contains(tx));
+            if chain_work(&chain) <= current_bc.total_work() {
+                println!(
+                    "[Warning] Received blockchain does not have more work than current blockchain"
+                );
+                // keep the blocks as a side branch, the node switches to it if the branch gets more work later
+                current_bc.add_side_blocks(blocks);
+                return Ok(UpdateBlockchainResponse {
+                    success: false,
+                    chain_length: current_bc.chain.len() as u32,
+                    total_work: current_bc.total_work().to_string(),
+                });
+            }
+            match current_bc.replace_chain(chain) {
+                Ok(true) => {}
+                Ok(false) => {
+                    println!("[Warning] Received blockchain spends more than the account balances");
+                    return Err(Status::invalid_argument(
+                        "Received blockchain spends more than the account balances",
+                    ));
+                }
+                Err(e) => {
+                    println!("[Warning] Failed to store the received blockchain: {}", e);
+                    return Err(Status::internal("Failed to store the received blockchain"));
+                }
+            }
 
             println!("[INFO] Updated blockchain from other peer.");
 
-            match self.tx.send(true).await {
-                Ok(_) => Ok(Response::new(UpdateBlockchainResponse {
+            // stop the current mining, a signal that is still pending is enough to stop it
+            match self.tx.try_send(true) {
+                Ok(_) | Err(TrySendError::Full(_)) => Ok(UpdateBlockchainResponse {
                     success: true,
                     chain_length: current_bc.chain.len() as u32,
-                })),
+                    total_work: current_bc.total_work().to_string(),
+                }),
 
-                Err(_) => Ok(Response::new(UpdateBlockchainResponse {
+                Err(_) => Ok(UpdateBlockchainResponse {
                     success: false,
                     chain_length: current_bc.chain.len() as u32,
-                })),
+                    total_work: current_bc.total_work().to_string(),
+                }),
+            }
+        }
+    }
+
+    /// Keep the blocks that are received before their previous blocks in the orphan pool, and request the missing blocks from the sender
+    /// Only blocks with a valid proof of work are kept, at a difficulty that the retarget schedule can reach from the current chain
+    async fn add_orphans(&self, blocks: Vec<Block>, sender: Option<NodeInfo>) {
+        let first_id = blocks[0].id as usize;
+        let blockchain = self.node.blockchain.lock().await;
+        let height = blockchain.chain.len();
+        let params = blockchain.params.clone();
+        let next_difficulty = blockchain.next_difficulty();
+        drop(blockchain);
+
+        if !blocks
+            .iter()
+            .all(|block| block.check_block_validity(&params))
+        {
+            println!("[Warning] Invalid orphan block received");
+            return;
+        }
+        let retarget_interval = params.difficulty.retarget_interval.max(1) as usize;
+        let gap = (first_id.saturating_sub(height) / retarget_interval) as i32;
+        let min_difficulty = (next_difficulty - gap - 1).max(1);
+        if blocks.iter().any(|block| block.difficulty < min_difficulty) {
+            println!("[Warning] Orphan block difficulty is too low");
+            return;
+        }
+
+        let mut orphans = self.node.orphans.lock().await;
+        for block in blocks {
+            orphans.add(block);
+        }
+        println!("[INFO] Orphan pool size: {}", orphans.len());
+        drop(orphans);
+
+        // only one request for missing blocks runs per peer at a time
+        if let Some(sender) = sender {
+            if self
+                .node
+                .block_fetches
+                .lock()
+                .await
+                .insert(sender.id.clone())
+            {
+                tokio::spawn(self.clone().fetch_missing_blocks(sender, height, first_id));
+            }
+        }
+    }
+
+    /// Request the blocks between the current chain and the orphan blocks from a peer, then connect the orphan blocks
+    async fn fetch_missing_blocks(self, peer: NodeInfo, start: usize, end: usize) {
+        let blocks = self.get_blocks(&peer, start, end).await;
+        self.node.block_fetches.lock().await.remove(&peer.id);
+        let Some(blocks) = blocks else {
+            return;
+        };
+        // go through the gRPC handler, which also connects the orphan blocks
+        let request = Request::new(UpdateBlockchainRequest { blocks, node: None });
+        if !matches!(self.update_blockchain(request).await, Ok(res) if res.get_ref().success) {
+            println!(
+                "[Warning] Missing blocks from node {} were not accepted",
+                peer.url()
+            );
+        }
+    }
+
+    /// Request the blocks from the start height up to the end height from a peer, page by page
+    /// The peer cannot send more blocks than were requested
+    async fn get_blocks(&self, peer: &NodeInfo, start: usize, end: usize) -> Option<Vec<Block>> {
+        let mut blocks = Vec::new();
+        while start + blocks.len() < end {
+            let request = Request::new(GetBlocksRequest {
+                start: (start + blocks.len()) as u32,
+                end: end as u32,
+            });
+            match self
+                .node
+                .peer_manager
+                .call(peer, |mut client| async move {
+                    client.get_blocks(request).await
+                })
+                .await
+            {
+                Ok(response) => {
+                    if response.blocks.is_empty() {
+                        break;
+                    }
+                    let missing = end - start - blocks.len();
+                    blocks.extend(response.blocks.into_iter().take(missing));
+                }
+                Err(error) => {
+                    println!(
+                        "[Warning] Failed to get missing blocks from node: {}, {}",
+                        peer.url(),
+                        error.message()
+                    );
+                    return None;
+                }
+            }
+        }
+        Some(blocks)
+    }
+
+    /// Get the peer that sent a request, if the node named in the request is a known peer at the address the request came from
+    /// The missing blocks are only requested from this peer, so a request cannot make the node dial another address
+    async fn request_sender(
+        &self,
+        node: Option<NodeInfo>,
+        remote_addr: Option<SocketAddr>,
+    ) -> Option<NodeInfo> {
+        let (node, remote_ip) = (node?, remote_addr?.ip().to_canonical());
+        let peers = self.node.peers.lock().await;
+        let peer = peers.iter().find(|peer| peer.id == node.id)?;
+        let ip = peer.ip.parse::<IpAddr>().ok()?;
+        (ip == remote_ip || (ip.is_loopback() && remote_ip.is_loopback())).then(|| peer.clone())
+    }
+
+    /// Connect the orphan blocks that follow the last block of the blockchain
+    async fn connect_orphans(&self) {
+        loop {
+            let tip = match self.node.blockchain.lock().await.chain.last() {
+                Some(block) => block.hash.clone(),
+                None => return,
+            };
+            let blocks = self.node.orphans.lock().await.take_children(&tip);
+            if blocks.is_empty() {
+                return;
+            }
+            match self.receive_blocks(blocks, None).await {
+                Ok(response) if response.success => {
+                    println!("[INFO] Connected orphan blocks to the blockchain")
+                }
+                _ => return,
             }
         }
     }
+}
+
+/// Implement the NodeMessage trait for the BlockchainNetwork struct
+/// This will allow the BlockchainNetwork struct to be used as a gRPC service
+#[tonic::async_trait]
+impl NodeMessage for Network {
+    async fn join_network(
+        &self,
+        request: Request<JoinNetworkRequest>,
+    ) -> Result<Response<JoinNetworkResponse>, Status> {
+        let req = request.into_inner();
+        let req_node = req.node.unwrap();
+        let current_bc = self.node.blockchain.lock().await;
+        if req.network_magic != current_bc.params.network_magic
+            || req.genesis_hash != current_bc.params.genesis_hash()
+        {
+            println!(
+                "[Warning] Node {:?} tried to join from another network",
+                req_node.port
+            );
+            return Err(Status::failed_precondition(
+                "The node belongs to another network",
+            ));
+        }
+
+        let mut peers = self.node.peers.lock().await;
+        peers.push(req_node.clone());
+
+        let reply = JoinNetworkResponse {
+            nodes: (*peers).clone(),
+            chain: current_bc.chain.clone(),
+            transactions: current_bc.mempool.to_vec(),
+            node: Some(self.node.info()),
+        };
+        println!("[INFO] New node joined the network: {}", req_node.url());
+        Ok(Response::new(reply))
+    }
+
+    /// Receive the blockchain from another node, if the received blockchain has more accumulated work than the current blockchain, then replace the current blockchain with the received blockchain
+    /// On equal work the current blockchain is kept, so the node does not switch between chains that are equally good
+    async fn update_blockchain(
+        &self,
+        request: Request<UpdateBlockchainRequest>,
+    ) -> Result<Response<UpdateBlockchainResponse>, Status> {
+        let remote_addr = request.remote_addr();
+        let req = request.into_inner();
+        let sender = self.request_sender(req.node, remote_addr).await;
+        let response = self.receive_blocks(req.blocks, sender).await?;
+        // the received blocks may fill the gap before some orphan blocks
+        if response.success {
+            self.connect_orphans().await;
+        }
+        Ok(Response::new(response))
+    }
 
     /// Receive the transactions from another node and add them to the current node's transaction pool
     async fn update_transaction(
@@ -142,16 +409,25 @@ impl NodeMessage for Network {
         request: Request<UpdateTransactionRequest>,
     ) -> Result<Response<UpdateTransactionResponse>, Status> {
         let mut blockchain = self.node.blockchain.lock().await;
+        let mut rejected = Vec::new();
         for transaction in request.into_inner().transactions {
-            if blockchain.transactions.contains(&transaction) {
+            // the peers relay the transactions to each other, so a known transaction is not an error
+            if blockchain.mempool.contains(&transaction) {
                 continue;
             }
-            if transaction.check_transaction_validity() {
-                blockchain.transactions.push(transaction);
+            if let Err(reason) = add_transaction(&mut blockchain, transaction.clone()) {
+                println!("[INFO] Transaction from other peer rejected: {}", reason);
+                rejected.push(RejectedTransaction {
+                    id: transaction.id,
+                    reason: reason.to_string(),
+                });
             }
         }
         println!("[INFO] Update transaction from other peer");
-        Ok(Response::new(UpdateTransactionResponse { success: true }))
+        Ok(Response::new(UpdateTransactionResponse {
+            success: rejected.is_empty(),
+            rejected,
+        }))
     }
 
     /// Receive the transactions from client and broadcast them to the network
@@ -160,37 +436,107 @@ impl NodeMessage for Network {
         request: Request<UpdateTransactionRequest>,
     ) -> Result<Response<UpdateTransactionResponse>, Status> {
         let mut blockchain = self.node.blockchain.lock().await;
-        let req_transaction = request.into_inner().transactions;
-
-        for transaction in &req_transaction {
-            if blockchain.transactions.contains(&transaction) {
-                continue;
-            }
-            if transaction.check_transaction_validity() {
-                blockchain.transactions.push(transaction.clone());
-            } else {
-                println!("[INFO] Invalid transaction recieved from client");
+        let mut req_transaction = Vec::new();
+        let mut rejected = Vec::new();
+        for transaction in request.into_inner().transactions {
+            match add_transaction(&mut blockchain, transaction.clone()) {
+                Ok(()) => req_transaction.push(transaction),
+                Err(reason) => {
+                    println!(
+                        "[INFO] Transaction recieved from client rejected: {}",
+                        reason
+                    );
+                    rejected.push(RejectedTransaction {
+                        id: transaction.id,
+                        reason: reason.to_string(),
+                    });
+                }
             }
         }
 
         println!("[INFO] New transaction recieved from client");
+        let response = UpdateTransactionResponse {
+            success: rejected.is_empty(),
+            rejected,
+        };
+        if req_transaction.is_empty() {
+            return Ok(Response::new(response));
+        }
 
-        // broadcast the new transaction to the rest of the network
-        for node in self.node.peers.lock().await.iter() {
-            if node.port == self.node.port {
-                continue;
-            }
-            let mut client = NodeMessageClient::connect(format!("http://127.0.0.1:{}", node.port))
-                .await
-                .unwrap();
-            client
-                .update_transaction(Request::new(UpdateTransactionRequest {
-                    transactions: req_transaction.clone(),
-                }))
-                .await
-                .unwrap();
+        // broadcast the new transaction to the rest of the network, off the blockchain lock and without waiting for the peers
+        drop(blockchain);
+        self.node.broadcast_transactions(req_transaction).await;
+        Ok(Response::new(response))
+    }
+
+    /// Return the merkle proof that a transaction is included in the blockchain, so that the client does not need the whole blockchain to verify it
+    async fn get_transaction_proof(
+        &self,
+        request: Request<GetTransactionProofRequest>,
+    ) -> Result<Response<GetTransactionProofResponse>, Status> {
+        let transaction_hash = request.into_inner().transaction_hash;
+        let blockchain = self.node.blockchain.lock().await;
+        for block in blockchain.chain.iter().rev() {
+            let index = match block
+                .transactions
+                .iter()
+                .position(|tx| tx.hash == transaction_hash)
+            {
+                Some(index) => index,
+                None => continue,
+            };
+            let hashes: Vec<String> = block
+                .transactions
+                .iter()
+                .map(|tx| tx.hash.clone())
+                .collect();
+            return Ok(Response::new(GetTransactionProofResponse {
+                transaction: Some(block.transactions[index].clone()),
+                header: Some(block.header()),
+                branch: merkle_branch(&hashes, index),
+                index: index as u32,
+                confirmations: (blockchain.chain.len() - block.id as usize) as u32,
+            }));
         }
-        Ok(Response::new(UpdateTransactionResponse { success: true }))
+        Err(Status::not_found("Transaction is not in the blockchain"))
+    }
+
+    /// Return the blocks between the requested heights, so that a node can fill the gap before its orphan blocks
+    async fn get_blocks(
+        &self,
+        request: Request<GetBlocksRequest>,
+    ) -> Result<Response<GetBlocksResponse>, Status> {
+        let req = request.into_inner();
+        let blockchain = self.node.blockchain.lock().await;
+        let end = (req.end as usize).min(blockchain.chain.len());
+        Ok(Response::new(GetBlocksResponse {
+            blocks: blockchain
+                .chain
+                .iter()
+                .take(end)
+                .skip(req.start as usize)
+                .take(MAX_BLOCKS)
+                .cloned()
+                .collect(),
+        }))
+    }
+
+    /// Return the block headers from the requested height, so that light clients can follow the blockchain without downloading the transactions
+    async fn get_headers(
+        &self,
+        request: Request<GetHeadersRequest>,
+    ) -> Result<Response<GetHeadersResponse>, Status> {
+        let start = request.into_inner().start as usize;
+        let blockchain = self.node.blockchain.lock().await;
+        Ok(Response::new(GetHeadersResponse {
+            headers: blockchain
+                .chain
+                .iter()
+                .skip(start)
+                .take(MAX_HEADERS)
+                .map(|block| block.header())
+                .collect(),
+        }))
     }
 
     /// Return the current blockchain to the client
@@ -204,11 +550,17 @@ impl NodeMessage for Network {
         }))
     }
     /// Generate a transaction and return it to the client
+    /// Without a nonce in the request, the transaction takes the next nonce of the sender that was not handed out yet
     async fn generate_transaction(
         &self,
         request: Request<GenerateTransactionRequest>,
     ) -> Result<Response<GenerateTransactionResponse>, Status> {
         let req = request.into_inner();
+        // the next nonce after the pending transactions of the sender, unless the caller picks one
+        let nonce = match req.nonce {
+            Some(nonce) => nonce,
+            None => self.node.blockchain.lock().await.next_nonce(&req.sender),
+        };
         let mut transaction = Transaction {
             id: req.id,
             sender: req.sender,
@@ -221,6 +573,7 @@ impl NodeMessage for Network {
                 .as_secs() as u32,
             hash: "".to_string(),
             signature: "".to_string(),
+            nonce,
         };
         let private_key_string = hex::decode(req.private_key).unwrap();
         let private_key = Rsa::private_key_from_pem(&private_key_string).unwrap();
@@ -252,7 +605,112 @@ impl NodeMessage for Network {
     ) -> Result<Response<GetTransactionListResponse>, Status> {
         let blockchain = self.node.blockchain.lock().await;
         Ok(Response::new(GetTransactionListResponse {
-            transactions: blockchain.transactions.clone(),
+            transactions: blockchain.mempool.to_vec(),
+        }))
+    }
+    /// Mine the requested number of blocks on top of the blockchain right away, and send them to the peers
+    /// Only allowed on networks that mine on demand, where the blocks are mined at the minimal difficulty
+    async fn generate_blocks(
+        &self,
+        request: Request<GenerateBlocksRequest>,
+    ) -> Result<Response<GenerateBlocksResponse>, Status> {
+        let req = request.into_inner();
+        let mut blockchain = self.node.blockchain.lock().await;
+        if !blockchain.params.mine_on_demand {
+            return Err(Status::failed_precondition(
+                "Blocks are only generated on networks that mine on demand",
+            ));
+        }
+        if req.count > blockchain.params.max_generated_blocks {
+            return Err(Status::invalid_argument(format!(
+                "At most {} blocks are generated at once",
+                blockchain.params.max_generated_blocks
+            )));
+        }
+        let miner_address = if req.miner_address.is_empty() {
+            self.node.miner_address.clone()
+        } else {
+            req.miner_address
+        };
+        blockchain.expire_transactions();
+        drop(blockchain);
+
+        // nothing else mines on this node, so the mining is never stopped
+        let (_tx, mut rx) = mpsc::channel::<bool>(1);
+        let mut blocks = Vec::new();
+        while blocks.len() < req.count as usize {
+            // the blockchain is only locked to build the template and to add the block, not while mining
+            let blockchain = self.node.blockchain.lock().await;
+            let last_block = blockchain.chain.last().unwrap().clone();
+            let block = block_template(
+                &last_block,
+                blockchain.block_transactions(&miner_address),
+                blockchain.next_difficulty(),
+                next_timestamp(&blockchain.chain),
+            );
+            drop(blockchain);
+            let block = self.node.miner.mine_block(block, &mut rx).await?;
+            let mut blockchain = self.node.blockchain.lock().await;
+            match blockchain.push_block(block.clone()) {
+                Ok(true) => blocks.push(block),
+                // the chain moved on while mining, the block is mined again on the new tip
+                Ok(false) if blockchain.chain.last().unwrap().hash != block.prev_hash => {}
+                Ok(false) => {
+                    return Err(Status::internal(
+                        "Generated block does not fit the blockchain",
+                    ))
+                }
+                Err(e) => {
+                    println!("[Warning] Failed to store the generated block: {}", e);
+                    return Err(Status::internal("Failed to store the generated block"));
+                }
+            }
+        }
+        println!("[INFO] Generated {} blocks", blocks.len());
+        // the blocks are sent off the blockchain lock, and the response waits for them so the caller sees them on the peers
+        if !blocks.is_empty() {
+            let snapshot = ChainSnapshot::new(&*self.node.blockchain.lock().await);
+            self.node.broadcast_chain(snapshot).await.wait().await;
+        }
+        Ok(Response::new(GenerateBlocksResponse { blocks }))
+    }
+    /// Return the mining statistics of the node to the client
+    async fn get_mining_info(
+        &self,
+        _: Request<GetMiningInfoRequest>,
+    ) -> Result<Response<GetMiningInfoResponse>, Status> {
+        Ok(Response::new(self.node.miner.stats.info()))
+    }
+    /// Return the balance and the nonces of an account to the client
+    async fn get_account(
+        &self,
+        request: Request<GetAccountRequest>,
+    ) -> Result<Response<GetAccountResponse>, Status> {
+        let account = request.into_inner().account;
+        let blockchain = self.node.blockchain.lock().await;
+        Ok(Response::new(GetAccountResponse {
+            balance: blockchain.ledger.balance(&account),
+            nonce: blockchain.ledger.nonce(&account),
+            pending_nonce: blockchain.next_nonce(&account),
         }))
     }
 }
+
+/// Check the hash and the signature of a transaction, and add it to the transaction pool
+/// A transaction that bumps the fee of a pending transaction replaces it
+fn add_transaction(
+    blockchain: &mut Blockchain,
+    transaction: Transaction,
+) -> Result<(), RejectReason> {
+    if !transaction.check_transaction_validity() {
+        return Err(RejectReason::Invalid);
+    }
+    let fee = transaction.fee;
+    if let Some(replaced) = blockchain.add_transaction(transaction)? {
+        println!(
+            "[INFO] Transaction {} replaced, fee bumped from {} to {}",
+            replaced.id, replaced.fee, fee
+        );
+    }
+    Ok(())
+}
@@ -2,8 +2,25 @@
 //! Node model is used to represent a node in the network
 //! The node has a list of peers, a blockchain, an ip, a port, and an id
 //! The node is used to store the state of the node in the network
+//! The node listens on its listen address and advertises its external address, which the other nodes dial to reach it
+use crate::models::archive::import_chain;
 use crate::models::blockchain::Blockchain;
-use crate::node::NodeInfo;
+use crate::models::broadcast::{
+    Broadcaster, ChainSnapshot, Delivery, Outbound, MAX_CONCURRENT_SENDS, MAX_QUEUED_MESSAGES,
+};
+use crate::models::chain_params::ChainParams;
+use crate::models::miner::{default_backend, default_threads, Miner};
+use crate::models::orphan::OrphanPool;
+use crate::models::peers::{
+    PeerManager, CONNECT_TIMEOUT, MAX_BACKOFF, MIN_BACKOFF, REQUEST_TIMEOUT,
+};
+use crate::models::storage::BlockStore;
+use crate::node::{NodeInfo, Transaction};
+use std::collections::HashSet;
+use std::net::{IpAddr, Ipv4Addr};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::Mutex;
 use uuid::Uuid;
 pub struct Node {
@@ -12,16 +29,181 @@ pub struct Node {
     pub ip: String,
     pub port: u32,
     pub id: Uuid,
+    pub miner_address: String,
+    pub orphans: Mutex<OrphanPool>,
+    /// The peers that missing blocks are being requested from, so only one request runs per peer
+    pub block_fetches: Mutex<HashSet<String>>,
+    /// The miner of the blocks of this node, with its statistics
+    pub miner: Miner,
+    /// The channels to the peers, which every request to a peer goes through
+    pub peer_manager: Arc<PeerManager>,
+    /// The outbound queues of the peers, which the new blocks and transactions are sent through
+    pub broadcaster: Broadcaster,
+}
+
+/// The configuration used to start a node
+#[derive(Clone, Debug)]
+pub struct NodeConfig {
+    /// The port to listen on
+    pub port: u16,
+    /// The port of a peer node to join the network through
+    pub peer_port: Option<u16>,
+    /// The IP address of the peer node
+    pub peer_ip: String,
+    /// The IP address to listen on
+    pub listen_ip: IpAddr,
+    /// The IP address advertised to the other nodes, defaults to the listen address
+    pub external_ip: Option<String>,
+    /// The initial balances of the accounts, keyed by public key
+    pub allocations: Vec<(String, i64)>,
+    /// The address that receives the rewards of the mined blocks, defaults to the node id
+    pub miner_address: Option<String>,
+    /// The consensus rules of the network to join
+    pub params: ChainParams,
+    /// Only follow the block headers of the peer node, without mining or serving other nodes
+    pub light_client: bool,
+    /// The hashes of the transactions a light client checks with merkle proofs whenever its headers change
+    pub watch_transactions: Vec<String>,
+    /// The directory where the blockchain is stored, the blockchain is only kept in memory if not set
+    pub data_dir: Option<PathBuf>,
+    /// A chain archive to import when the node starts, the node must not have any block yet
+    pub import_chain: Option<PathBuf>,
+    /// The number of threads the CPU miner splits the nonce space across
+    pub mining_threads: usize,
+    /// The time a peer has to answer a request
+    pub request_timeout: Duration,
+}
+
+impl NodeConfig {
+    pub fn new(port: u16, peer_port: Option<u16>) -> NodeConfig {
+        NodeConfig {
+            port,
+            peer_port,
+            peer_ip: Ipv4Addr::LOCALHOST.to_string(),
+            listen_ip: IpAddr::V4(Ipv4Addr::LOCALHOST),
+            external_ip: None,
+            allocations: Vec::new(),
+            miner_address: None,
+            params: ChainParams::default(),
+            light_client: false,
+            watch_transactions: Vec::new(),
+            data_dir: None,
+            import_chain: None,
+            mining_threads: default_threads(),
+            request_timeout: REQUEST_TIMEOUT,
+        }
+    }
+
+    /// Get the URL of the peer node to join the network through
+    pub fn peer_url(&self) -> Option<String> {
+        self.peer_port
+            .map(|port| node_url(&self.peer_ip, port as u32))
+    }
+
+    /// Get the IP address the other nodes dial to reach this node
+    pub fn advertised_ip(&self) -> String {
+        self.external_ip
+            .clone()
+            .unwrap_or_else(|| self.listen_ip.to_string())
+    }
 }
 
 impl Node {
-    pub fn new(port: u32) -> Node {
+    pub fn new(config: &NodeConfig) -> Node {
+        let id = Uuid::new_v4();
+        let mut blockchain =
+            Blockchain::with_params(config.params.clone(), config.allocations.clone());
+        if let Some(dir) = &config.data_dir {
+            let storage = BlockStore::open(dir).expect("Failed to open the block store");
+            blockchain
+                .open_storage(storage)
+                .expect("Failed to load the stored blockchain");
+            println!(
+                "[INFO] Loaded {} blocks from {}",
+                blockchain.chain.len(),
+                dir.display()
+            );
+        }
+        if let Some(path) = &config.import_chain {
+            if blockchain.chain.len() > 1 {
+                panic!(
+                    "A chain archive can only be imported into a node with only the genesis block"
+                );
+            }
+            match import_chain(path, &mut blockchain) {
+                Ok(height) => println!("[INFO] Imported {} blocks from {}", height, path.display()),
+                Err(e) => panic!("Failed to import {}: {}", path.display(), e),
+            }
+        }
+        let peer_manager = Arc::new(PeerManager::new(
+            CONNECT_TIMEOUT,
+            config.request_timeout,
+            MIN_BACKOFF,
+            MAX_BACKOFF,
+        ));
+        let info = NodeInfo {
+            id: id.to_string(),
+            ip: config.advertised_ip(),
+            port: config.port as u32,
+        };
         Node {
             peers: Mutex::new(Vec::new()),
-            blockchain: Mutex::new(Blockchain::new()),
-            ip: "127.0.0.1".to_string(),
-            port: port,
-            id: Uuid::new_v4(),
+            blockchain: Mutex::new(blockchain),
+            ip: info.ip.clone(),
+            port: info.port,
+            id,
+            miner_address: config
+                .miner_address
+                .clone()
+                .unwrap_or_else(|| id.to_string()),
+            orphans: Mutex::new(OrphanPool::default()),
+            block_fetches: Mutex::new(HashSet::new()),
+            miner: Miner::new(default_backend(config.mining_threads)),
+            broadcaster: Broadcaster::new(
+                info,
+                peer_manager.clone(),
+                MAX_QUEUED_MESSAGES,
+                MAX_CONCURRENT_SENDS,
+            ),
+            peer_manager,
         }
     }
+
+    /// Get the information other nodes use to reach this node
+    pub fn info(&self) -> NodeInfo {
+        NodeInfo {
+            id: self.id.to_string(),
+            ip: self.ip.clone(),
+            port: self.port,
+        }
+    }
+
+    /// Queue the new tip of the blockchain for every peer, from a snapshot taken while the blockchain was locked
+    pub async fn broadcast_chain(&self, snapshot: ChainSnapshot) -> Delivery {
+        let peers = self.peers.lock().await;
+        self.broadcaster
+            .broadcast(&peers, Outbound::Chain(Arc::new(snapshot)))
+    }
+
+    /// Queue the new transactions for every peer
+    pub async fn broadcast_transactions(&self, transactions: Vec<Transaction>) -> Delivery {
+        let peers = self.peers.lock().await;
+        self.broadcaster
+            .broadcast(&peers, Outbound::Transactions(Arc::new(transactions)))
+    }
+}
+
+impl NodeInfo {
+    /// Get the URL to dial the node at its advertised address
+    pub fn url(&self) -> String {
+        node_url(&self.ip, self.port)
+    }
+}
+
+/// Get the URL to dial a node listening on the given IP address and port
+pub fn node_url(ip: &str, port: u32) -> String {
+    match ip.parse::<IpAddr>() {
+        Ok(IpAddr::V6(ip)) => format!("http://[{}]:{}", ip, port),
+        _ => format!("http://{}:{}", ip, port),
+    }
 }
@@ -0,0 +1,86 @@
+//! ## Orphan module
+//! An orphan block is a block received before its previous block, so it cannot be connected to the blockchain yet
+//! The orphan pool keeps these blocks until the missing blocks are received, then they are connected to the blockchain
+//! The pool is limited in size and in age, so peers cannot use it to exhaust the memory of the node
+use crate::node::Block;
+use std::time::{Duration, Instant};
+
+/// The default maximum number of blocks in the orphan pool
+pub const MAX_ORPHAN_BLOCKS: usize = 100;
+/// The default time an orphan block is kept while waiting for its previous blocks
+pub const MAX_ORPHAN_AGE: Duration = Duration::from_secs(10 * 60);
+
+pub struct OrphanPool {
+    pub max_blocks: usize,
+    pub max_age: Duration,
+    blocks: Vec<(Block, Instant)>,
+}
+
+impl Default for OrphanPool {
+    fn default() -> OrphanPool {
+        OrphanPool::new(MAX_ORPHAN_BLOCKS, MAX_ORPHAN_AGE)
+    }
+}
+
+impl OrphanPool {
+    pub fn new(max_blocks: usize, max_age: Duration) -> OrphanPool {
+        OrphanPool {
+            max_blocks,
+            max_age,
+            blocks: Vec::new(),
+        }
+    }
+
+    /// Get the number of blocks in the pool
+    pub fn len(&self) -> usize {
+        self.blocks.len()
+    }
+
+    /// Check if the pool is empty
+    pub fn is_empty(&self) -> bool {
+        self.blocks.is_empty()
+    }
+
+    /// Check if the block with the given hash is in the pool
+    pub fn contains(&self, hash: &str) -> bool {
+        self.blocks.iter().any(|(block, _)| block.hash == hash)
+    }
+
+    /// Add a block to the pool, the oldest block is dropped when the pool is full
+    /// Return false if the block is already in the pool
+    pub fn add(&mut self, block: Block) -> bool {
+        self.prune();
+        if self.max_blocks == 0 || self.contains(&block.hash) {
+            return false;
+        }
+        if self.blocks.len() >= self.max_blocks {
+            self.blocks.remove(0);
+        }
+        self.blocks.push((block, Instant::now()));
+        true
+    }
+
+    /// Remove and return the orphan blocks that follow the block with the given hash, in chain order
+    pub fn take_children(&mut self, hash: &str) -> Vec<Block> {
+        self.prune();
+        let mut children = Vec::new();
+        let mut prev_hash = hash.to_string();
+        while let Some(index) = self
+            .blocks
+            .iter()
+            .position(|(block, _)| block.prev_hash == prev_hash)
+        {
+            let (block, _) = self.blocks.remove(index);
+            prev_hash = block.hash.clone();
+            children.push(block);
+        }
+        children
+    }
+
+    /// Drop the blocks that have been waiting for longer than the maximum age
+    pub fn prune(&mut self) {
+        let max_age = self.max_age;
+        self.blocks
+            .retain(|(_, received)| received.elapsed() <= max_age);
+    }
+}
@@ -0,0 +1,176 @@
+//! ## Peer module
+//! The peer manager keeps a long-lived gRPC channel to every peer, so the node does not connect again for every request
+//! Every call to a peer must answer within the request timeout, so a hung peer cannot block the node
+//! A peer that is unreachable or does not answer in time is backed off: it is not called again until the backoff delay passes,
+//! and the delay doubles with every failure in a row, up to the maximum delay, then the channel is opened again
+//! The failures are returned to the caller as a status and logged, they never stop the node
+use crate::node::node_message_client::NodeMessageClient;
+use crate::node::NodeInfo;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tonic::transport::{Channel, Endpoint};
+use tonic::{Code, Response, Status};
+
+/// The default time to open a connection to a peer
+pub const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+/// The default time a peer has to answer a request
+pub const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+/// The default delay before calling a peer again after a failure
+pub const MIN_BACKOFF: Duration = Duration::from_secs(1);
+/// The default maximum delay before calling a peer again after failures in a row
+pub const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+struct Peer {
+    url: String,
+    /// The channel to the peer, opened again after a failure
+    client: Option<NodeMessageClient<Channel>>,
+    /// The number of failed calls in a row
+    failures: u32,
+    /// The time before which the peer is not called, after a failure
+    retry_at: Option<Instant>,
+}
+
+pub struct PeerManager {
+    pub connect_timeout: Duration,
+    pub request_timeout: Duration,
+    pub min_backoff: Duration,
+    pub max_backoff: Duration,
+    /// The peers that were called, keyed by node id
+    peers: Mutex<HashMap<String, Peer>>,
+}
+
+impl Default for PeerManager {
+    fn default() -> PeerManager {
+        PeerManager::new(CONNECT_TIMEOUT, REQUEST_TIMEOUT, MIN_BACKOFF, MAX_BACKOFF)
+    }
+}
+
+impl PeerManager {
+    pub fn new(
+        connect_timeout: Duration,
+        request_timeout: Duration,
+        min_backoff: Duration,
+        max_backoff: Duration,
+    ) -> PeerManager {
+        PeerManager {
+            connect_timeout,
+            request_timeout,
+            min_backoff,
+            max_backoff,
+            peers: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Call a peer with its client, the call fails if the peer does not answer within the request timeout
+    /// A peer that is backed off after failures is not called, the call fails right away
+    pub async fn call<T, F, Fut>(&self, peer: &NodeInfo, call: F) -> Result<T, Status>
+    where
+        F: FnOnce(NodeMessageClient<Channel>) -> Fut,
+        Fut: Future<Output = Result<Response<T>, Status>>,
+    {
+        let client = self.client(peer).map_err(|status| *status)?;
+        let result = match tokio::time::timeout(self.request_timeout, call(client)).await {
+            Ok(result) => result.map(Response::into_inner),
+            Err(_) => Err(Status::deadline_exceeded(format!(
+                "Node {} did not answer within {:?}",
+                peer.url(),
+                self.request_timeout
+            ))),
+        };
+        match &result {
+            Err(status) if is_unreachable(status) => self.record_failure(peer),
+            _ => self.record_success(peer),
+        }
+        result
+    }
+
+    /// Check if a peer is backed off after failures, so it is not called yet
+    pub fn is_backed_off(&self, peer: &NodeInfo) -> bool {
+        let peers = self.peers.lock().unwrap();
+        peers
+            .get(&peer.id)
+            .and_then(|state| state.retry_at)
+            .is_some_and(|retry_at| Instant::now() < retry_at)
+    }
+
+    /// Get the number of failed calls in a row to a peer
+    pub fn failures(&self, peer: &NodeInfo) -> u32 {
+        let peers = self.peers.lock().unwrap();
+        peers.get(&peer.id).map_or(0, |state| state.failures)
+    }
+
+    /// Get the client of a peer, opening its channel if it has none
+    fn client(&self, peer: &NodeInfo) -> Result<NodeMessageClient<Channel>, Box<Status>> {
+        let url = peer.url();
+        let mut peers = self.peers.lock().unwrap();
+        let state = peers.entry(peer.id.clone()).or_insert_with(|| Peer {
+            url: url.clone(),
+            client: None,
+            failures: 0,
+            retry_at: None,
+        });
+        // a peer that advertises a new address is reached there
+        if state.url != url {
+            state.url = url.clone();
+            state.client = None;
+        }
+        if let Some(retry_at) = state.retry_at {
+            let now = Instant::now();
+            if now < retry_at {
+                return Err(Box::new(Status::unavailable(format!(
+                    "Node {} is backed off for {:?} after {} failures",
+                    url,
+                    retry_at - now,
+                    state.failures
+                ))));
+            }
+        }
+        if let Some(client) = &state.client {
+            return Ok(client.clone());
+        }
+        // the channel connects on the first request, and reconnects on its own while the peer answers
+        let channel = Endpoint::from_shared(url.clone())
+            .map_err(|e| {
+                Box::new(Status::invalid_argument(format!(
+                    "Invalid node address {}: {}",
+                    url, e
+                )))
+            })?
+            .connect_timeout(self.connect_timeout)
+            .connect_lazy();
+        let client = NodeMessageClient::new(channel);
+        state.client = Some(client.clone());
+        Ok(client)
+    }
+
+    /// Reset the backoff of a peer after it answered
+    fn record_success(&self, peer: &NodeInfo) {
+        if let Some(state) = self.peers.lock().unwrap().get_mut(&peer.id) {
+            state.failures = 0;
+            state.retry_at = None;
+        }
+    }
+
+    /// Back off a peer after it failed to answer, and drop its channel so it is opened again after the backoff
+    fn record_failure(&self, peer: &NodeInfo) {
+        if let Some(state) = self.peers.lock().unwrap().get_mut(&peer.id) {
+            state.failures += 1;
+            let backoff = self
+                .min_backoff
+                .saturating_mul(1 << (state.failures - 1).min(16))
+                .min(self.max_backoff);
+            state.retry_at = Some(Instant::now() + backoff);
+            state.client = None;
+        }
+    }
+}
+
+/// Check if a failed call means the peer cannot be reached, rather than the peer refusing the request
+fn is_unreachable(status: &Status) -> bool {
+    matches!(
+        status.code(),
+        Code::Unavailable | Code::DeadlineExceeded | Code::Unknown
+    )
+}
@@ -0,0 +1,228 @@
+//! ## Storage module
+//! The block store keeps the blockchain and the transaction pool on disk, so that a restarted node does not need to download them again
+//! The blocks are appended to a block file, each record is the length of the encoded block followed by the block
+//! The index maps every height of the chain to the hash, offset and length of its block, and is the only source of truth for which blocks are in the chain
+//! The index and the transaction pool are written to a temporary file and renamed, so a crash in the middle of a write leaves the previous version intact
+//! The transaction pool is written in the background at most once every write interval, so adding a transaction does not wait for the disk
+//! Blocks appended after the last indexed block are left over from an interrupted write, and are truncated when the store is opened
+use crate::node::{Block, Transaction};
+use prost::Message;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+const BLOCKS_FILE: &str = "blocks.dat";
+const INDEX_FILE: &str = "index";
+const TRANSACTIONS_FILE: &str = "transactions.dat";
+
+/// How often the transaction pool is written to disk when it changed
+pub const TRANSACTIONS_WRITE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The location of a block in the block file
+#[derive(Clone, Debug, PartialEq)]
+struct IndexEntry {
+    hash: String,
+    offset: u64,
+    length: u64,
+}
+
+pub struct BlockStore {
+    dir: PathBuf,
+    blocks: File,
+    index: Vec<IndexEntry>,
+}
+
+impl BlockStore {
+    /// Open the block store in the given directory, creating it if it does not exist
+    pub fn open(dir: impl AsRef<Path>) -> io::Result<BlockStore> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir)?;
+        let index = read_index(&dir.join(INDEX_FILE))?;
+        let blocks = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(dir.join(BLOCKS_FILE))?;
+
+        // drop the blocks written after the last index update
+        let end = index
+            .iter()
+            .map(|entry| entry.offset + entry.length)
+            .max()
+            .unwrap_or(0);
+        if blocks.metadata()?.len() < end {
+            return Err(invalid_data("The block file is shorter than the index"));
+        }
+        blocks.set_len(end)?;
+        Ok(BlockStore { dir, blocks, index })
+    }
+
+    /// Get the number of blocks in the stored chain
+    pub fn height(&self) -> usize {
+        self.index.len()
+    }
+
+    /// Read the stored block at the given height
+    pub fn read_block(&mut self, height: usize) -> io::Result<Option<Block>> {
+        let entry = match self.index.get(height) {
+            Some(entry) => entry.clone(),
+            None => return Ok(None),
+        };
+        self.blocks.seek(SeekFrom::Start(entry.offset))?;
+        let mut buf = vec![0; entry.length as usize];
+        self.blocks.read_exact(&mut buf)?;
+        let block = decode_record::<Block>(&buf)?;
+        if block.hash != entry.hash {
+            return Err(invalid_data("The stored block does not match the index"));
+        }
+        Ok(Some(block))
+    }
+
+    /// Read the whole stored chain
+    pub fn read_chain(&mut self) -> io::Result<Vec<Block>> {
+        let mut chain = Vec::with_capacity(self.height());
+        for height in 0..self.height() {
+            if let Some(block) = self.read_block(height)? {
+                chain.push(block);
+            }
+        }
+        Ok(chain)
+    }
+
+    /// Replace the stored blocks from the given height with the given blocks
+    /// The blocks are appended to the block file before the index is switched to them
+    pub fn write_blocks(&mut self, start: usize, blocks: &[Block]) -> io::Result<()> {
+        let mut offset = self.blocks.metadata()?.len();
+        let mut index = self.index[..start.min(self.index.len())].to_vec();
+        let mut buf = Vec::new();
+        for block in blocks {
+            let record = encode_record(block);
+            index.push(IndexEntry {
+                hash: block.hash.clone(),
+                offset,
+                length: record.len() as u64,
+            });
+            offset += record.len() as u64;
+            buf.extend(record);
+        }
+        self.blocks.write_all(&buf)?;
+        self.blocks.sync_data()?;
+
+        let mut contents = String::new();
+        for (height, entry) in index.iter().enumerate() {
+            contents.push_str(&format!(
+                "{} {} {} {}\n",
+                height, entry.hash, entry.offset, entry.length
+            ));
+        }
+        write_atomic(&self.dir, INDEX_FILE, contents.as_bytes())?;
+        self.index = index;
+        Ok(())
+    }
+
+    /// Read the stored transaction pool
+    pub fn read_transactions(&self) -> io::Result<Vec<Transaction>> {
+        let buf = match fs::read(self.dir.join(TRANSACTIONS_FILE)) {
+            Ok(buf) => buf,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+        let mut transactions = Vec::new();
+        let mut rest = buf.as_slice();
+        while !rest.is_empty() {
+            let length = record_length(rest)?;
+            transactions.push(decode_record::<Transaction>(&rest[..length])?);
+            rest = &rest[length..];
+        }
+        Ok(transactions)
+    }
+
+    /// Prepare the replacement of the stored transaction pool, which is written later without the block store
+    pub fn transactions_write(&self, transactions: &[Transaction]) -> TransactionsWrite {
+        TransactionsWrite {
+            dir: self.dir.clone(),
+            contents: transactions.iter().flat_map(encode_record).collect(),
+        }
+    }
+}
+
+/// An encoded transaction pool waiting to replace the stored one
+pub struct TransactionsWrite {
+    dir: PathBuf,
+    contents: Vec<u8>,
+}
+
+impl TransactionsWrite {
+    /// Replace the stored transaction pool
+    pub fn write(&self) -> io::Result<()> {
+        write_atomic(&self.dir, TRANSACTIONS_FILE, &self.contents)
+    }
+}
+
+/// Write a file by renaming a fully written temporary file over it
+fn write_atomic(dir: &Path, name: &str, contents: &[u8]) -> io::Result<()> {
+    let tmp = dir.join(format!("{}.tmp", name));
+    let mut file = File::create(&tmp)?;
+    file.write_all(contents)?;
+    file.sync_all()?;
+    fs::rename(&tmp, dir.join(name))?;
+    File::open(dir)?.sync_all()
+}
+
+/// Read the index file, a missing file is an empty chain
+fn read_index(path: &Path) -> io::Result<Vec<IndexEntry>> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    let mut index = Vec::new();
+    for line in BufReader::new(file).lines() {
+        let line = line?;
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let entry = match fields.as_slice() {
+            [height, hash, offset, length] if height.parse() == Ok(index.len()) => IndexEntry {
+                hash: hash.to_string(),
+                offset: offset.parse().map_err(|_| invalid_data("Invalid offset"))?,
+                length: length.parse().map_err(|_| invalid_data("Invalid length"))?,
+            },
+            _ => return Err(invalid_data("Invalid index entry")),
+        };
+        index.push(entry);
+    }
+    Ok(index)
+}
+
+/// Encode a message as a record, prefixed with its length
+pub(crate) fn encode_record(message: &impl Message) -> Vec<u8> {
+    let body = message.encode_to_vec();
+    let mut record = (body.len() as u32).to_le_bytes().to_vec();
+    record.extend(body);
+    record
+}
+
+/// Get the length of the record at the start of the buffer
+pub(crate) fn record_length(buf: &[u8]) -> io::Result<usize> {
+    let prefix: [u8; 4] = buf
+        .get(..4)
+        .and_then(|prefix| prefix.try_into().ok())
+        .ok_or_else(|| invalid_data("Truncated record"))?;
+    let length = 4 + u32::from_le_bytes(prefix) as usize;
+    if buf.len() < length {
+        return Err(invalid_data("Truncated record"));
+    }
+    Ok(length)
+}
+
+/// Decode a record written by `encode_record`
+pub(crate) fn decode_record<M: Message + Default>(record: &[u8]) -> io::Result<M> {
+    if record_length(record)? != record.len() {
+        return Err(invalid_data("Invalid record length"));
+    }
+    M::decode(&record[4..]).map_err(|e| invalid_data(&e.to_string()))
+}
+
+pub(crate) fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
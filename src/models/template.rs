@@ -0,0 +1,122 @@
+//! ## Block template module
+//! The block template builder picks the pooled transactions of the next block, the ones paying the most fee per byte first
+//! The transactions of a sender are included in nonce order, so a later transaction only goes in with the earlier ones of its sender
+//! A block orders its transactions by timestamp, so the timestamps of a sender must increase with its nonces to be included
+//! A sender's transactions are scored as a package, so a high fee transaction also pulls in the low fee transactions it depends on
+//! The transactions that do not fit the block stay in the pool for the next blocks
+use crate::models::blockchain::transaction_size;
+use crate::node::Transaction;
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet, VecDeque};
+
+/// The total fee and size of a run of transactions of one sender
+#[derive(Clone, Copy, Debug, Default)]
+struct Package {
+    fee: i64,
+    size: usize,
+    len: usize,
+}
+
+impl Package {
+    /// Compare the fee per byte of two packages, then their total fee
+    fn cmp_score(&self, other: &Package) -> Ordering {
+        let rate =
+            (self.fee as i128 * other.size as i128).cmp(&(other.fee as i128 * self.size as i128));
+        rate.then(self.fee.cmp(&other.fee))
+    }
+}
+
+/// Select the pooled transactions of the next block, within the maximum total size in bytes and the maximum count
+/// The transactions are returned in the timestamp order the block requires, and no two of them share a timestamp
+pub fn select_transactions(
+    pool: &[Transaction],
+    max_size: usize,
+    max_count: usize,
+) -> Vec<Transaction> {
+    let sizes: Vec<usize> = pool.iter().map(transaction_size).collect();
+
+    // the pending transactions of every sender, in the order they must be included
+    let mut order: Vec<usize> = (0..pool.len()).collect();
+    order.sort_by_key(|&i| (pool[i].nonce, i));
+    let mut senders = HashMap::new();
+    let mut queues: Vec<VecDeque<usize>> = Vec::new();
+    let mut stalled = HashSet::new();
+    for i in order {
+        let queue = *senders.entry(pool[i].sender.as_str()).or_insert_with(|| {
+            queues.push(VecDeque::new());
+            queues.len() - 1
+        });
+        // a transaction older than the previous one of its sender would come first in the block, out of nonce order
+        if queues[queue]
+            .back()
+            .is_some_and(|&prev| pool[prev].timestamp >= pool[i].timestamp)
+        {
+            stalled.insert(queue);
+        }
+        if !stalled.contains(&queue) {
+            queues[queue].push_back(i);
+        }
+    }
+
+    let mut selected = Vec::new();
+    let mut timestamps = HashSet::new();
+    let mut size = 0;
+    loop {
+        let room = (max_size - size, max_count - selected.len());
+        let mut best: Option<(usize, Package)> = None;
+        for (q, queue) in queues.iter_mut().enumerate() {
+            if queue.is_empty() {
+                continue;
+            }
+            let Some(package) = best_package(pool, queue, &sizes, room) else {
+                // the next transaction of the sender does not fit, so neither do the later ones
+                queue.clear();
+                continue;
+            };
+            // on equal scores the package whose first transaction arrived first wins
+            if best.is_none_or(|(_, best)| package.cmp_score(&best) == Ordering::Greater) {
+                best = Some((q, package));
+            }
+        }
+        let Some((q, package)) = best else {
+            break;
+        };
+        for _ in 0..package.len {
+            let i = queues[q].pop_front().unwrap();
+            if !timestamps.insert(pool[i].timestamp) {
+                // the later transactions of the sender depend on this one, so they wait for the next block as well
+                queues[q].clear();
+                break;
+            }
+            size += sizes[i];
+            selected.push(i);
+        }
+    }
+
+    selected.sort_by_key(|&i| pool[i].timestamp);
+    selected.into_iter().map(|i| pool[i].clone()).collect()
+}
+
+/// Get the run of pending transactions of a sender, from the first one, that pays the most fee per byte
+/// Only the runs that fit in the room left in the block, in bytes and transactions, are considered
+fn best_package(
+    pool: &[Transaction],
+    queue: &VecDeque<usize>,
+    sizes: &[usize],
+    (room_size, room_count): (usize, usize),
+) -> Option<Package> {
+    let mut package = Package::default();
+    let mut best: Option<Package> = None;
+    for &i in queue {
+        package.fee += pool[i].fee as i64;
+        package.size += sizes[i];
+        package.len += 1;
+        if package.size > room_size || package.len > room_count {
+            break;
+        }
+        if best.is_none_or(|best| package.cmp_score(&best) == Ordering::Greater) {
+            best = Some(package);
+        }
+    }
+    best
+}
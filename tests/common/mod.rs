@@ -1,4 +1,12 @@
+#![allow(dead_code)]
+use blockchain::models::blockchain::{block_template, next_timestamp, RewardSchedule};
+use blockchain::models::chain_params::ChainParams;
+use blockchain::models::node::NodeConfig;
+use blockchain::node::node_message_client::NodeMessageClient;
+use blockchain::node::{Block, Transaction};
 use openssl::rsa::Rsa;
+use prost::Message;
+use tonic::transport::Channel;
 pub struct Client {
     pub public_key: String,
     pub private_key: String,
@@ -20,3 +28,106 @@ fn generate_keypair() -> (String, String) {
     let private_key = hex::encode(rsa.private_key_to_pem().unwrap());
     (public_key, private_key)
 }
+
+/// Node configuration where the client holds an initial balance
+pub fn funded_config(port: u16, peer_port: Option<u16>, client: &Client) -> NodeConfig {
+    let mut config = NodeConfig::new(port, peer_port);
+    config.allocations.push((client.public_key.clone(), 1000));
+    config
+}
+
+/// Node configuration on the regtest network where the client holds an initial balance
+pub fn regtest_config(port: u16, peer_port: Option<u16>, client: &Client) -> NodeConfig {
+    let mut config = funded_config(port, peer_port, client);
+    config.params = ChainParams::regtest();
+    config
+}
+
+/// Connect to the node listening on the given port
+pub async fn connect(port: u16) -> NodeMessageClient<Channel> {
+    NodeMessageClient::connect(format!("http://127.0.0.1:{}", port))
+        .await
+        .expect("Failed to connect to node")
+}
+
+/// Build a transaction with the given timestamp, the mempool and the ledger do not check the signatures
+pub fn transaction_at(
+    id: &str,
+    sender: &str,
+    nonce: u64,
+    amount: i32,
+    fee: i32,
+    timestamp: u32,
+) -> Transaction {
+    let mut transaction = Transaction {
+        id: id.to_string(),
+        sender: sender.to_string(),
+        receiver: "receiver".to_string(),
+        amount,
+        fee,
+        timestamp,
+        hash: String::new(),
+        signature: String::new(),
+        nonce,
+    };
+    transaction.hash = transaction.compute_hash();
+    transaction
+}
+
+/// Build a transaction, the mempool and the ledger do not check the signatures
+pub fn transaction(id: &str, sender: &str, nonce: u64, amount: i32, fee: i32) -> Transaction {
+    transaction_at(id, sender, nonce, amount, fee, 10)
+}
+
+/// Get the number of bytes a transaction adds to an encoded block
+pub fn size(transaction: &Transaction) -> usize {
+    Block {
+        transactions: vec![transaction.clone()],
+        ..Block::default()
+    }
+    .encoded_len()
+}
+
+/// Build a block at the given difficulty that only pays the block reward to the miner
+pub fn template(difficulty: i32) -> Block {
+    let last_block = Block {
+        id: -1,
+        ..Block::default()
+    };
+    let transactions = vec![Transaction::coinbase(
+        "miner",
+        0,
+        &RewardSchedule::default(),
+        &[],
+    )];
+    block_template(&last_block, transactions, difficulty, next_timestamp(&[]))
+}
+
+/// Build a chain of blocks on top of the genesis block that only pay the block reward to the miner
+pub fn mine_chain(length: usize, miner: &str) -> Vec<Block> {
+    let params = ChainParams::default();
+    let mut chain = vec![params.genesis.clone()];
+    for i in 1..=length {
+        let mut block = Block {
+            id: i as i32,
+            timestamp: params.genesis.timestamp + i as u32 * 10,
+            prev_hash: chain.last().unwrap().hash.clone(),
+            difficulty: params.difficulty.next_difficulty(&chain),
+            transactions: vec![Transaction::coinbase(miner, i as i32, &params.reward, &[])],
+            ..Block::default()
+        };
+        block.merkle_root = block.compute_merkle_root();
+        loop {
+            block.hash = block.compute_hash(block.nonce);
+            if block
+                .hash
+                .starts_with(&"0".repeat(block.difficulty as usize))
+            {
+                break;
+            }
+            block.nonce += 1;
+        }
+        chain.push(block);
+    }
+    chain
+}
@@ -0,0 +1,134 @@
+mod common;
+use blockchain::models::chain_params::ChainParams;
+use blockchain::models::node::node_url;
+use blockchain::node::node_message_client::NodeMessageClient;
+use blockchain::node::{
+    GenerateBlocksRequest, GenerateTransactionRequest, GetBlockchainRequest, GetPeerListRequest,
+    GetTransactionListRequest, UpdateTransactionRequest,
+};
+use blockchain::start_with_config;
+use std::net::{IpAddr, Ipv4Addr};
+use std::time::Duration;
+use tonic::Request;
+use uuid::Uuid;
+
+#[test]
+fn test_node_url() {
+    assert_eq!(node_url("10.0.0.1", 50000), "http://10.0.0.1:50000");
+    assert_eq!(node_url("::1", 50000), "http://[::1]:50000");
+    assert_eq!(node_url("node.example", 50000), "http://node.example:50000");
+}
+
+#[tokio::test]
+async fn test_advertised_address() {
+    let client = common::Client::new();
+    // two of the nodes listen on the same port of different addresses, so only their advertised address tells them apart
+    let nodes = [
+        (Ipv4Addr::new(127, 0, 0, 2), 50000),
+        (Ipv4Addr::LOCALHOST, 50000),
+        (Ipv4Addr::LOCALHOST, 50001),
+    ];
+    let mut tasks = Vec::new();
+    for (i, (ip, port)) in nodes.into_iter().enumerate() {
+        let mut config = common::funded_config(port, None, &client);
+        config.params = ChainParams::regtest();
+        config.listen_ip = IpAddr::V4(ip);
+        if let Some((peer_ip, peer_port)) = i.checked_sub(1).map(|peer| nodes[peer]) {
+            config.peer_ip = peer_ip.to_string();
+            config.peer_port = Some(peer_port);
+        }
+        tasks.push(tokio::spawn(start_with_config(config)));
+        tokio::time::sleep(Duration::from_millis(200)).await;
+    }
+    let urls: Vec<String> = nodes
+        .iter()
+        .map(|(ip, port)| node_url(&ip.to_string(), *port as u32))
+        .collect();
+
+    // the last node reaches the first one through the address it advertises
+    for url in &urls {
+        let mut peers: Vec<String> = NodeMessageClient::connect(url.clone())
+            .await
+            .expect("Failed to connect to node")
+            .get_peer_list(Request::new(GetPeerListRequest {}))
+            .await
+            .unwrap()
+            .into_inner()
+            .nodes
+            .iter()
+            .map(|peer| peer.url())
+            .collect();
+        peers.sort();
+        let mut expected = urls.clone();
+        expected.sort();
+        assert_eq!(peers, expected);
+    }
+
+    // a transaction sent to the last node is relayed to both nodes on the same port
+    let mut grpc_client = NodeMessageClient::connect(urls[2].clone())
+        .await
+        .expect("Failed to connect to node");
+    let transactions = vec![grpc_client
+        .generate_transaction(Request::new(GenerateTransactionRequest {
+            id: Uuid::new_v4().to_string(),
+            sender: client.public_key.clone(),
+            private_key: client.private_key.clone(),
+            receiver: "receiver".to_string(),
+            amount: 100,
+            fee: 1,
+            nonce: None,
+        }))
+        .await
+        .unwrap()
+        .into_inner()
+        .transaction
+        .unwrap()];
+    let res = grpc_client
+        .update_client_transaction(Request::new(UpdateTransactionRequest {
+            transactions: transactions.clone(),
+        }))
+        .await
+        .unwrap()
+        .into_inner();
+    assert!(res.success);
+    // wait for the transaction to be relayed to the peers
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    for url in &urls {
+        let pool = NodeMessageClient::connect(url.clone())
+            .await
+            .expect("Failed to connect to node")
+            .get_transaction_list(Request::new(GetTransactionListRequest {}))
+            .await
+            .unwrap()
+            .into_inner()
+            .transactions;
+        assert_eq!(pool, transactions);
+    }
+
+    // the block mined by the first node reaches the other nodes
+    NodeMessageClient::connect(urls[0].clone())
+        .await
+        .expect("Failed to connect to node")
+        .generate_blocks(Request::new(GenerateBlocksRequest {
+            count: 1,
+            miner_address: String::new(),
+        }))
+        .await
+        .unwrap();
+    for url in &urls {
+        let chain = NodeMessageClient::connect(url.clone())
+            .await
+            .expect("Failed to connect to node")
+            .get_blockchain(Request::new(GetBlockchainRequest {}))
+            .await
+            .unwrap()
+            .into_inner()
+            .chain;
+        assert_eq!(chain.len(), 2);
+        assert_eq!(chain[1].transactions[1..], transactions);
+    }
+    for task in tasks {
+        task.abort();
+    }
+}
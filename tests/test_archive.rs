@@ -0,0 +1,44 @@
+mod common;
+use blockchain::models::archive::{export_chain, import_chain, read_chain, ImportError};
+use blockchain::models::blockchain::Blockchain;
+use blockchain::models::node::{Node, NodeConfig};
+use uuid::Uuid;
+
+#[tokio::test]
+async fn test_export_import() {
+    let dir = std::env::temp_dir().join(Uuid::new_v4().to_string());
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("chain.dat");
+    let chain = common::mine_chain(3, "miner");
+    export_chain(&path, &chain).unwrap();
+    assert_eq!(read_chain(&path).unwrap(), chain);
+
+    // a fresh node imports the chain and its balances
+    let mut config = NodeConfig::new(50000, None);
+    config.import_chain = Some(path.clone());
+    let node = Node::new(&config);
+    let blockchain = node.blockchain.lock().await;
+    assert_eq!(blockchain.chain, chain);
+    assert_eq!(blockchain.ledger.balance("miner"), 150);
+
+    // the first invalid block is reported, and the chain is not imported
+    let mut tampered = chain.clone();
+    tampered[1].transactions[0].amount = 1000;
+    export_chain(&path, &tampered).unwrap();
+    let mut blockchain = Blockchain::new();
+    match import_chain(&path, &mut blockchain) {
+        Err(ImportError::InvalidBlock(height)) => assert_eq!(height, 1),
+        result => panic!("Unexpected import result: {:?}", result),
+    }
+    assert_eq!(blockchain.chain.len(), 1);
+
+    // an archive with an unknown format version is refused
+    let mut buf = std::fs::read(&path).unwrap();
+    buf[4] = 2;
+    std::fs::write(&path, buf).unwrap();
+    assert!(matches!(
+        import_chain(&path, &mut blockchain),
+        Err(ImportError::Io(_))
+    ));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,103 @@
+mod common;
+use blockchain::models::blockchain::Blockchain;
+use blockchain::models::chain_params::ChainParams;
+use blockchain::models::template::select_transactions;
+use blockchain::node::Transaction;
+
+/// Build a pooled transaction, the template builder does not check the signatures
+fn transaction(sender: &str, nonce: u64, fee: i32, timestamp: u32) -> Transaction {
+    let id = format!("{}-{}", sender, timestamp);
+    common::transaction_at(&id, sender, nonce, 10, fee, timestamp)
+}
+
+/// Get the ids of the transactions
+fn ids(transactions: &[Transaction]) -> Vec<&str> {
+    transactions.iter().map(|tx| tx.id.as_str()).collect()
+}
+
+#[test]
+fn test_fee_priority() {
+    let params = ChainParams {
+        max_block_transactions: 3,
+        ..ChainParams::regtest()
+    };
+    let allocations = ["alice", "bob", "carol"]
+        .iter()
+        .map(|account| (account.to_string(), 1000))
+        .collect();
+    let mut blockchain = Blockchain::with_params(params, allocations);
+    for tx in [
+        transaction("alice", 0, 1, 10),
+        transaction("bob", 0, 5, 11),
+        transaction("carol", 0, 3, 12),
+    ] {
+        assert!(blockchain.add_transaction(tx).is_ok());
+    }
+
+    // the coinbase and the two transactions paying the most fee, in timestamp order
+    let transactions = blockchain.block_transactions("miner");
+    assert!(transactions[0].is_coinbase());
+    assert_eq!(transactions[0].amount, 50 + 5 + 3);
+    assert_eq!(ids(&transactions[1..]), ["bob-11", "carol-12"]);
+    // the transaction left out stays in the pool
+    assert_eq!(blockchain.mempool.len(), 3);
+}
+
+#[test]
+fn test_sender_dependencies() {
+    // the high fee transaction of alice needs her earlier transaction, which pays no fee
+    let pool = vec![
+        transaction("alice", 0, 0, 10),
+        transaction("alice", 1, 10, 11),
+        transaction("bob", 0, 4, 12),
+    ];
+    let all = select_transactions(&pool, usize::MAX, usize::MAX);
+    assert_eq!(ids(&all), ["alice-10", "alice-11", "bob-12"]);
+
+    // the package of alice pays 5 per transaction, more than bob
+    let two = select_transactions(&pool, usize::MAX, 2);
+    assert_eq!(ids(&two), ["alice-10", "alice-11"]);
+
+    // the package of alice does not fit, and her first transaction alone pays less than bob
+    let one = select_transactions(&pool, usize::MAX, 1);
+    assert_eq!(ids(&one), ["bob-12"]);
+
+    // a later transaction of a sender is never included without the earlier ones
+    let first = common::size(&pool[0]);
+    let pool = &pool[..2];
+    assert_eq!(
+        ids(&select_transactions(pool, first, usize::MAX)),
+        ["alice-10"]
+    );
+    assert!(select_transactions(pool, first - 1, usize::MAX).is_empty());
+
+    // the block orders the transactions by timestamp, so a later nonce with an earlier timestamp waits for the next block
+    let pool = vec![
+        transaction("alice", 1, 10, 10),
+        transaction("alice", 0, 0, 11),
+    ];
+    assert_eq!(
+        ids(&select_transactions(&pool, usize::MAX, usize::MAX)),
+        ["alice-11"]
+    );
+}
+
+#[test]
+fn test_block_size_and_timestamps() {
+    // the transactions have the same size, so the two paying the most fill the size of two
+    let pool = vec![
+        transaction("alice", 0, 1, 10),
+        transaction("bobby", 0, 3, 11),
+        transaction("carol", 0, 2, 12),
+    ];
+    assert!(pool
+        .iter()
+        .all(|tx| common::size(tx) == common::size(&pool[0])));
+    let selected = select_transactions(&pool, 2 * common::size(&pool[0]), usize::MAX);
+    assert_eq!(ids(&selected), ["bobby-11", "carol-12"]);
+
+    // the timestamps of a block must strictly increase, so only one of two transactions with the same timestamp is selected
+    let pool = vec![transaction("alice", 0, 1, 10), transaction("bob", 0, 3, 10)];
+    let selected = select_transactions(&pool, usize::MAX, usize::MAX);
+    assert_eq!(ids(&selected), ["bob-10"]);
+}
@@ -0,0 +1,95 @@
+use blockchain::models::blockchain::{
+    check_header_timestamp_validity, check_timestamp_validity, median_time_past, mine_new_block,
+    next_timestamp, RewardSchedule, MAX_FUTURE_BLOCK_TIME,
+};
+use blockchain::node::node_message_client::NodeMessageClient;
+use blockchain::node::Block;
+use blockchain::node::Transaction;
+use blockchain::node::UpdateBlockchainRequest;
+use blockchain::start;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tonic::Request;
+
+#[test]
+fn test_median_time_past() {
+    // the median of the last 11 blocks, which are not sorted by timestamp
+    let chain: Vec<Block> = [50, 1, 2, 3, 4, 5, 11, 10, 9, 8, 7, 6]
+        .iter()
+        .map(|timestamp| Block {
+            timestamp: *timestamp,
+            ..Block::default()
+        })
+        .collect();
+    assert_eq!(median_time_past(&[]), 0);
+    assert_eq!(median_time_past(&chain), 6);
+
+    assert!(!check_timestamp_validity(&chain, 6));
+    assert!(check_timestamp_validity(&chain, 7));
+    assert!(next_timestamp(&chain) > median_time_past(&chain));
+
+    // a block can not be too far in the future
+    let now = next_timestamp(&[]);
+    assert!(check_timestamp_validity(&chain, now + 60));
+    assert!(!check_timestamp_validity(
+        &chain,
+        now + MAX_FUTURE_BLOCK_TIME + 60
+    ));
+
+    // the light client applies the same rules to the headers
+    let headers: Vec<_> = chain.iter().map(Block::header).collect();
+    assert!(!check_header_timestamp_validity(&headers, 6));
+    assert!(check_header_timestamp_validity(&headers, 7));
+    assert!(!check_header_timestamp_validity(
+        &headers,
+        now + MAX_FUTURE_BLOCK_TIME + 60
+    ));
+}
+
+#[tokio::test]
+async fn test_block_timestamp_rejected() {
+    let task = tokio::spawn(start(50000, None));
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    let last_block = Block {
+        id: -1,
+        ..Block::default()
+    };
+    let transactions = vec![Transaction::coinbase(
+        "miner",
+        0,
+        &RewardSchedule::default(),
+        &[],
+    )];
+    let (_tx, mut rx) = mpsc::channel::<bool>(1);
+    let mut grpc_client = NodeMessageClient::connect("http://127.0.0.1:50000")
+        .await
+        .expect("Failed to connect to node");
+
+    // a block mined with a timestamp too far in the future is rejected
+    let future = next_timestamp(&[]) + MAX_FUTURE_BLOCK_TIME + 60;
+    let block = mine_new_block(&last_block, transactions.clone(), 2, future, &mut rx)
+        .await
+        .unwrap();
+    assert!(grpc_client
+        .update_blockchain(Request::new(UpdateBlockchainRequest {
+            blocks: vec![block],
+            node: None,
+        }))
+        .await
+        .is_err());
+
+    // the timestamp is covered by the proof of work, so it can not be changed after mining
+    let mut block = mine_new_block(&last_block, transactions, 2, next_timestamp(&[]), &mut rx)
+        .await
+        .unwrap();
+    block.timestamp -= 1;
+    assert!(grpc_client
+        .update_blockchain(Request::new(UpdateBlockchainRequest {
+            blocks: vec![block],
+            node: None,
+        }))
+        .await
+        .is_err());
+    task.abort();
+}
@@ -0,0 +1,118 @@
+use blockchain::models::blockchain::Blockchain;
+use blockchain::models::chain_params::ChainParams;
+use blockchain::node::node_message_client::NodeMessageClient;
+use blockchain::node::{Block, GetBlockchainRequest, Transaction, UpdateBlockchainRequest};
+use blockchain::start;
+use std::time::Duration;
+use tonic::Request;
+
+/// Extend a chain with blocks that only pay the block reward to the miner
+fn mine_blocks(prefix: &[Block], count: usize, miner: &str) -> Vec<Block> {
+    let params = ChainParams::default();
+    let mut chain = prefix.to_vec();
+    for _ in 0..count {
+        let id = chain.len() as i32;
+        let mut block = Block {
+            id,
+            timestamp: params.genesis.timestamp + id as u32 * 10,
+            prev_hash: chain.last().map(|b| b.hash.clone()).unwrap_or_default(),
+            difficulty: params.difficulty.next_difficulty(&chain),
+            transactions: vec![Transaction::coinbase(miner, id, &params.reward, &[])],
+            ..Block::default()
+        };
+        block.merkle_root = block.compute_merkle_root();
+        loop {
+            block.hash = block.compute_hash(block.nonce);
+            if block
+                .hash
+                .starts_with(&"0".repeat(block.difficulty as usize))
+            {
+                break;
+            }
+            block.nonce += 1;
+        }
+        chain.push(block);
+    }
+    chain
+}
+
+#[test]
+fn test_reorg_events() {
+    let genesis = ChainParams::default().genesis;
+    let main_chain = mine_blocks(&[genesis], 3, "main");
+    let side_chain = mine_blocks(&main_chain[..2], 3, "side");
+    let mut blockchain = Blockchain::new();
+    let mut events = blockchain.subscribe();
+
+    assert!(blockchain.replace_chain(main_chain.clone()).unwrap());
+    let event = events.try_recv().unwrap();
+    assert_eq!(event.fork_height, 1);
+    assert!(event.disconnected.is_empty());
+    assert_eq!(event.connected, main_chain[1..]);
+
+    // the side branch is kept next to the active chain, the blocks of the active chain it carries are skipped
+    let mut received = side_chain[1..4].to_vec();
+    received.push(main_chain[3].clone());
+    blockchain.add_side_blocks(received);
+    assert_eq!(blockchain.chain, main_chain);
+    assert_eq!(blockchain.tree.len(), 2);
+    assert_eq!(blockchain.tree.tips().len(), 1);
+    assert_eq!(
+        blockchain.fork(&side_chain[3].hash, 3).unwrap(),
+        (2, side_chain[2..4].to_vec())
+    );
+    assert_eq!(
+        blockchain.fork(&main_chain[3].hash, 3).unwrap(),
+        (4, Vec::new())
+    );
+    assert!(blockchain.fork(&side_chain[3].hash, 4).is_none());
+    assert!(events.try_recv().is_err());
+
+    // switching to the side branch disconnects the blocks after the fork
+    assert!(blockchain.replace_chain(side_chain.clone()).unwrap());
+    let event = events.try_recv().unwrap();
+    assert_eq!(event.fork_height, 2);
+    assert_eq!(event.disconnected, main_chain[2..]);
+    assert_eq!(event.connected, side_chain[2..]);
+    // the disconnected blocks become the side branch
+    assert_eq!(blockchain.tree.len(), 2);
+    assert_eq!(blockchain.tree.branch(&main_chain[3].hash), main_chain[2..]);
+    assert_eq!(blockchain.ledger.balance("main"), 50);
+    assert_eq!(blockchain.ledger.balance("side"), 150);
+}
+
+#[tokio::test]
+async fn test_side_branch() {
+    let task = tokio::spawn(start(50000, None));
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    let genesis = ChainParams::default().genesis;
+    let main_chain = mine_blocks(&[genesis], 3, "main");
+    let side_chain = mine_blocks(&main_chain[..2], 3, "side");
+    let mut grpc_client = NodeMessageClient::connect("http://127.0.0.1:50000")
+        .await
+        .expect("Failed to connect to node");
+    for (blocks, success) in [
+        (main_chain.clone(), true),
+        // the side branch has as much work as the active chain, so it is only kept
+        (side_chain[2..4].to_vec(), false),
+        // the last block of the side branch is enough to switch to it
+        (side_chain[4..].to_vec(), true),
+    ] {
+        let response = grpc_client
+            .update_blockchain(Request::new(UpdateBlockchainRequest { blocks, node: None }))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(response.success, success);
+    }
+
+    let chain = grpc_client
+        .get_blockchain(Request::new(GetBlockchainRequest {}))
+        .await
+        .unwrap()
+        .into_inner()
+        .chain;
+    assert_eq!(chain, side_chain);
+    task.abort();
+}
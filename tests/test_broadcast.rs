@@ -0,0 +1,209 @@
+mod common;
+use blockchain::models::broadcast::MAX_CONCURRENT_SENDS;
+use blockchain::models::chain_params::ChainParams;
+use blockchain::node::{
+    GenerateBlocksRequest, GenerateTransactionRequest, GetBlockchainRequest, GetPeerListRequest,
+    GetTransactionListRequest, JoinNetworkRequest, NodeInfo, UpdateTransactionRequest,
+};
+use blockchain::start_with_config;
+use std::time::{Duration, Instant};
+use tokio::net::TcpListener;
+use tokio::task::JoinHandle;
+use tonic::Request;
+use uuid::Uuid;
+
+/// Get the length of the blockchain of a node
+async fn chain_length(port: u16) -> usize {
+    common::connect(port)
+        .await
+        .get_blockchain(Request::new(GetBlockchainRequest {}))
+        .await
+        .unwrap()
+        .into_inner()
+        .chain
+        .len()
+}
+
+/// Start a peer that accepts the connections but never answers
+async fn hung_peer(port: u16) -> JoinHandle<()> {
+    let listener = TcpListener::bind(format!("127.0.0.1:{}", port))
+        .await
+        .unwrap();
+    tokio::spawn(async move {
+        let mut connections = Vec::new();
+        while let Ok((stream, _)) = listener.accept().await {
+            connections.push(stream);
+        }
+    })
+}
+
+/// Add a peer to the peer list of a node
+async fn join(port: u16, peer: NodeInfo) {
+    let params = ChainParams::regtest();
+    common::connect(port)
+        .await
+        .join_network(Request::new(JoinNetworkRequest {
+            node: Some(peer),
+            network_magic: params.network_magic,
+            genesis_hash: params.genesis_hash().to_string(),
+        }))
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_hung_peer_does_not_delay_broadcast() {
+    let hung = hung_peer(50000).await;
+
+    let client = common::Client::new();
+    let mut config = common::funded_config(50001, None, &client);
+    config.params = ChainParams::regtest();
+    config.request_timeout = Duration::from_secs(5);
+    let mut tasks = vec![tokio::spawn(start_with_config(config))];
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    // the hung peer joins before the second node, so it comes first in the peer list
+    join(
+        50001,
+        NodeInfo {
+            id: Uuid::new_v4().to_string(),
+            ip: "127.0.0.1".to_string(),
+            port: 50000,
+        },
+    )
+    .await;
+    let mut config = common::funded_config(50002, Some(50001), &client);
+    config.params = ChainParams::regtest();
+    // the second node also introduces itself to the hung peer when it joins
+    config.request_timeout = Duration::from_millis(300);
+    tasks.push(tokio::spawn(start_with_config(config)));
+    tokio::time::sleep(Duration::from_millis(800)).await;
+
+    // the generated block waits on the hung peer before the response, but not before the other peer gets it
+    let generate = tokio::spawn(async {
+        common::connect(50001)
+            .await
+            .generate_blocks(Request::new(GenerateBlocksRequest {
+                count: 1,
+                miner_address: String::new(),
+            }))
+            .await
+            .unwrap()
+    });
+    let start = Instant::now();
+    while chain_length(50002).await < 2 {
+        assert!(
+            start.elapsed() < Duration::from_secs(2),
+            "The block did not reach the peer"
+        );
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+
+    // the node answers while the block is still being sent to the hung peer
+    assert!(!generate.is_finished());
+    assert_eq!(chain_length(50001).await, 2);
+    let peers = common::connect(50001)
+        .await
+        .get_peer_list(Request::new(GetPeerListRequest {}))
+        .await
+        .unwrap()
+        .into_inner()
+        .nodes;
+    assert_eq!(peers.len(), 3);
+    assert!(start.elapsed() < Duration::from_secs(2));
+
+    generate.await.unwrap();
+    hung.abort();
+    for task in tasks {
+        task.abort();
+    }
+}
+
+#[tokio::test]
+async fn test_hung_peers_do_not_delay_transactions() {
+    let hung = hung_peer(50003).await;
+    let client = common::Client::new();
+    let mut config = common::funded_config(50004, None, &client);
+    config.params = ChainParams::regtest();
+    config.request_timeout = Duration::from_secs(5);
+    let mut tasks = vec![tokio::spawn(start_with_config(config))];
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    // more hung peers than sends at once come before the other peer in the peer list
+    for _ in 0..=MAX_CONCURRENT_SENDS {
+        join(
+            50004,
+            NodeInfo {
+                id: Uuid::new_v4().to_string(),
+                ip: "127.0.0.1".to_string(),
+                port: 50003,
+            },
+        )
+        .await;
+    }
+    let mut config = common::funded_config(50005, None, &client);
+    config.params = ChainParams::regtest();
+    tasks.push(tokio::spawn(start_with_config(config)));
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    let peer = common::connect(50005)
+        .await
+        .get_peer_list(Request::new(GetPeerListRequest {}))
+        .await
+        .unwrap()
+        .into_inner()
+        .nodes
+        .pop()
+        .unwrap();
+    join(50004, peer).await;
+
+    // the client is answered once the transaction is pooled, without waiting for the peers
+    let mut grpc_client = common::connect(50004).await;
+    let transaction = grpc_client
+        .generate_transaction(Request::new(GenerateTransactionRequest {
+            id: Uuid::new_v4().to_string(),
+            sender: client.public_key.clone(),
+            private_key: client.private_key.clone(),
+            receiver: "receiver".to_string(),
+            amount: 100,
+            fee: 1,
+            nonce: None,
+        }))
+        .await
+        .unwrap()
+        .into_inner()
+        .transaction
+        .unwrap();
+    let start = Instant::now();
+    let res = grpc_client
+        .update_client_transaction(Request::new(UpdateTransactionRequest {
+            transactions: vec![transaction.clone()],
+        }))
+        .await
+        .unwrap()
+        .into_inner();
+    assert!(res.success);
+    assert!(start.elapsed() < Duration::from_millis(500));
+
+    // the hung peers give up their places in the limit of the sends, so the other peer gets the transaction
+    loop {
+        let pool = common::connect(50005)
+            .await
+            .get_transaction_list(Request::new(GetTransactionListRequest {}))
+            .await
+            .unwrap()
+            .into_inner()
+            .transactions;
+        if pool == [transaction.clone()] {
+            break;
+        }
+        assert!(
+            start.elapsed() < Duration::from_secs(3),
+            "The transaction did not reach the peer"
+        );
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    hung.abort();
+    for task in tasks {
+        task.abort();
+    }
+}
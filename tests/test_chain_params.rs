@@ -0,0 +1,135 @@
+use blockchain::models::archive::ImportError;
+use blockchain::models::blockchain::Blockchain;
+use blockchain::models::chain_params::ChainParams;
+use blockchain::node::node_message_client::NodeMessageClient;
+use blockchain::node::{Block, GetPeerListRequest, JoinNetworkRequest, NodeInfo, Transaction};
+use blockchain::start;
+use prost::Message;
+use std::time::Duration;
+use tonic::{Code, Request};
+
+#[test]
+fn test_genesis_blocks() {
+    let networks = ["mainnet", "testnet", "regtest"];
+    let presets: Vec<ChainParams> = networks
+        .iter()
+        .map(|name| ChainParams::from_name(name).unwrap())
+        .collect();
+    assert!(ChainParams::from_name("unknown").is_none());
+
+    // every network has its own valid genesis block
+    for (i, params) in presets.iter().enumerate() {
+        assert_eq!(params.name, networks[i]);
+        assert_eq!(params.genesis.id, 0);
+        assert_eq!(
+            params.genesis.merkle_root,
+            params.genesis.compute_merkle_root()
+        );
+        assert!(params.genesis.header().check_header_validity());
+        for other in &presets[i + 1..] {
+            assert_ne!(params.genesis_hash(), other.genesis_hash());
+            assert_ne!(params.network_magic, other.network_magic);
+        }
+        let blockchain = Blockchain::with_params(params.clone(), Vec::new());
+        assert_eq!(blockchain.chain, vec![params.genesis.clone()]);
+        assert_eq!(blockchain.first_invalid_block(), None);
+    }
+
+    // a chain from another network is refused
+    let mut blockchain = Blockchain::new();
+    assert!(!blockchain
+        .replace_chain(vec![ChainParams::testnet().genesis])
+        .unwrap());
+    assert_eq!(blockchain.chain, vec![ChainParams::mainnet().genesis]);
+}
+
+#[test]
+fn test_max_block_size() {
+    let params = ChainParams::regtest();
+    let mut block = Block {
+        id: 1,
+        timestamp: params.genesis.timestamp + 10,
+        prev_hash: params.genesis.hash.clone(),
+        difficulty: 1,
+        transactions: vec![Transaction::coinbase("miner", 1, &params.reward, &[])],
+        ..Block::default()
+    };
+    block.merkle_root = block.compute_merkle_root();
+    loop {
+        block.hash = block.compute_hash(block.nonce);
+        if block
+            .hash
+            .starts_with(&"0".repeat(block.difficulty as usize))
+        {
+            break;
+        }
+        block.nonce += 1;
+    }
+    let chain = vec![params.genesis.clone(), block.clone()];
+
+    // a block is valid while it stays within the maximum size
+    let size = block.encoded_len();
+    let fitting = ChainParams {
+        max_block_size: size,
+        ..params.clone()
+    };
+    assert!(block.check_block_validity(&fitting));
+    assert!(Blockchain::with_params(fitting, Vec::new())
+        .import_chain(chain.clone())
+        .is_ok());
+
+    // the same block is rejected by a network with a smaller maximum size
+    let small = ChainParams {
+        max_block_size: size - 1,
+        ..params
+    };
+    assert!(!block.check_block_validity(&small));
+    assert!(matches!(
+        Blockchain::with_params(small, Vec::new()).import_chain(chain),
+        Err(ImportError::InvalidBlock(1))
+    ));
+}
+
+#[tokio::test]
+async fn test_join_other_network() {
+    let task = tokio::spawn(start(50000, None));
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    let mut grpc_client = NodeMessageClient::connect("http://127.0.0.1:50000")
+        .await
+        .expect("Failed to connect to node");
+    let node = NodeInfo {
+        id: "testnet".to_string(),
+        ip: "127.0.0.1".to_string(),
+        port: 50001,
+    };
+    let testnet = ChainParams::testnet();
+    for (network_magic, genesis_hash) in [
+        (testnet.network_magic, testnet.genesis_hash().to_string()),
+        // the same network magic with another genesis block is still another network
+        (
+            ChainParams::mainnet().network_magic,
+            testnet.genesis_hash().to_string(),
+        ),
+    ] {
+        let status = grpc_client
+            .join_network(Request::new(JoinNetworkRequest {
+                node: Some(node.clone()),
+                network_magic,
+                genesis_hash,
+            }))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), Code::FailedPrecondition);
+    }
+
+    // the refused node is not added to the peer list
+    let peers = grpc_client
+        .get_peer_list(Request::new(GetPeerListRequest {}))
+        .await
+        .unwrap()
+        .into_inner()
+        .nodes;
+    assert_eq!(peers.len(), 1);
+    task.abort();
+}
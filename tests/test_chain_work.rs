@@ -0,0 +1,118 @@
+use blockchain::models::blockchain::{chain_work, DifficultySchedule};
+use blockchain::models::chain_params::ChainParams;
+use blockchain::models::node::NodeConfig;
+use blockchain::node::node_message_client::NodeMessageClient;
+use blockchain::node::Block;
+use blockchain::node::GetBlockchainRequest;
+use blockchain::node::Transaction;
+use blockchain::node::UpdateBlockchainRequest;
+use blockchain::start_with_config;
+use std::time::Duration;
+use tonic::Request;
+
+/// Build a chain on top of the genesis block where each block is mined the given number of seconds after the previous one
+fn mine_chain(params: &ChainParams, intervals: &[u32], miner: &str) -> Vec<Block> {
+    let mut chain = vec![params.genesis.clone()];
+    let mut timestamp = params.genesis.timestamp;
+    for interval in intervals {
+        timestamp += interval;
+        let mut block = Block {
+            id: chain.len() as i32,
+            timestamp,
+            prev_hash: chain.last().unwrap().hash.clone(),
+            difficulty: params.difficulty.next_difficulty(&chain),
+            transactions: vec![Transaction::coinbase(
+                miner,
+                chain.len() as i32,
+                &params.reward,
+                &[],
+            )],
+            ..Block::default()
+        };
+        block.merkle_root = block.compute_merkle_root();
+        loop {
+            block.hash = block.compute_hash(block.nonce);
+            if block
+                .hash
+                .starts_with(&"0".repeat(block.difficulty as usize))
+            {
+                break;
+            }
+            block.nonce += 1;
+        }
+        chain.push(block);
+    }
+    chain
+}
+
+#[tokio::test]
+async fn test_most_work_chain() {
+    // retarget every two blocks, so the chains can have different difficulties
+    let schedule = DifficultySchedule {
+        initial_difficulty: 1,
+        retarget_interval: 2,
+        target_block_time: 100,
+    };
+    // the regtest genesis block is mined at difficulty 1, as the schedule starts
+    let params = ChainParams {
+        difficulty: schedule,
+        ..ChainParams::regtest()
+    };
+    let mut config = NodeConfig::new(50000, None);
+    config.params = params.clone();
+    let task = tokio::spawn(start_with_config(config));
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    // a long chain with slow blocks stays at difficulty 1, a short chain with fast blocks raises it to 2
+    let easy_chain = mine_chain(&params, &[1000, 1, 1000, 1], "easy");
+    let hard_chain = mine_chain(&params, &[1, 1, 1], "hard");
+    assert_eq!(easy_chain[4].difficulty, 1);
+    assert_eq!(hard_chain[3].difficulty, 2);
+    assert!(chain_work(&hard_chain) > chain_work(&easy_chain));
+
+    let mut grpc_client = NodeMessageClient::connect("http://127.0.0.1:50000")
+        .await
+        .expect("Failed to connect to node");
+    let response = grpc_client
+        .update_blockchain(Request::new(UpdateBlockchainRequest {
+            blocks: easy_chain.clone(),
+            node: None,
+        }))
+        .await
+        .unwrap()
+        .into_inner();
+    assert!(response.success);
+
+    // the shorter chain replaces the longer one because it has more work
+    let response = grpc_client
+        .update_blockchain(Request::new(UpdateBlockchainRequest {
+            blocks: hard_chain.clone(),
+            node: None,
+        }))
+        .await
+        .unwrap()
+        .into_inner();
+    assert!(response.success);
+    assert_eq!(response.total_work, chain_work(&hard_chain).to_string());
+
+    // the longer chain is refused, and the node reports its total work
+    let response = grpc_client
+        .update_blockchain(Request::new(UpdateBlockchainRequest {
+            blocks: easy_chain.clone(),
+            node: None,
+        }))
+        .await
+        .unwrap()
+        .into_inner();
+    assert!(!response.success);
+    assert_eq!(response.total_work, chain_work(&hard_chain).to_string());
+
+    let chain = grpc_client
+        .get_blockchain(Request::new(GetBlockchainRequest {}))
+        .await
+        .unwrap()
+        .into_inner()
+        .chain;
+    assert_eq!(chain, hard_chain);
+    task.abort();
+}
@@ -0,0 +1,127 @@
+mod common;
+use blockchain::models::blockchain::{mine_new_block, next_timestamp, RewardSchedule};
+use blockchain::models::chain_params::ChainParams;
+use blockchain::node::node_message_client::NodeMessageClient;
+use blockchain::node::GenerateBlocksRequest;
+use blockchain::node::GenerateTransactionRequest;
+use blockchain::node::GetBlockchainRequest;
+use blockchain::node::Transaction;
+use blockchain::node::UpdateBlockchainRequest;
+use blockchain::node::UpdateTransactionRequest;
+use blockchain::start_with_config;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tonic::Request;
+use uuid::Uuid;
+
+#[tokio::test]
+async fn test_coinbase() {
+    let mut tasks = Vec::new();
+
+    // Create a client and create public key and private key
+    let client = common::Client::new();
+
+    let nodes = [50000, 50001];
+    let mut config = common::regtest_config(nodes[0], None, &client);
+    config.miner_address = Some("miner0".to_string());
+    tasks.push(tokio::spawn(start_with_config(config)));
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    let mut config = common::regtest_config(nodes[1], Some(nodes[0]), &client);
+    config.miner_address = Some("miner1".to_string());
+    tasks.push(tokio::spawn(start_with_config(config)));
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    let mut grpc_client = NodeMessageClient::connect(format!("http://127.0.0.1:{}", nodes[0]))
+        .await
+        .expect("Failed to connect to node");
+    let transaction = grpc_client
+        .generate_transaction(Request::new(GenerateTransactionRequest {
+            id: Uuid::new_v4().to_string(),
+            sender: client.public_key.clone(),
+            private_key: client.private_key.clone(),
+            receiver: "receiver".to_string(),
+            amount: 100,
+            fee: 3,
+            nonce: None,
+        }))
+        .await
+        .unwrap()
+        .into_inner()
+        .transaction
+        .unwrap();
+    grpc_client
+        .update_client_transaction(Request::new(UpdateTransactionRequest {
+            transactions: vec![transaction.clone()],
+        }))
+        .await
+        .unwrap();
+
+    // the first node mines the block with the transaction
+    grpc_client
+        .generate_blocks(Request::new(GenerateBlocksRequest {
+            count: 1,
+            miner_address: String::new(),
+        }))
+        .await
+        .unwrap();
+
+    // the first transaction pays the subsidy and the fee to the miner of the block
+    let blockchain = grpc_client
+        .get_blockchain(Request::new(GetBlockchainRequest {}))
+        .await
+        .unwrap()
+        .into_inner()
+        .chain;
+    assert_eq!(blockchain.len(), 2);
+    let coinbase = &blockchain[1].transactions[0];
+    assert!(coinbase.is_coinbase());
+    assert_eq!(coinbase.receiver, "miner0");
+    assert_eq!(coinbase.amount, 50 + 3);
+    assert_eq!(blockchain[1].transactions[1], transaction);
+
+    // a block paying more than the subsidy, or with more than one coinbase, is rejected
+    let reward = ChainParams::regtest().reward;
+    let inflated = RewardSchedule {
+        initial_subsidy: 1000,
+        ..reward.clone()
+    };
+    let bad_transactions = vec![
+        vec![Transaction::coinbase("miner", 2, &inflated, &[])],
+        vec![
+            Transaction::coinbase("miner", 2, &reward, &[]),
+            Transaction::coinbase("miner", 2, &reward, &[]),
+        ],
+    ];
+    let (_tx, mut rx) = mpsc::channel::<bool>(1);
+    for transactions in bad_transactions {
+        let bad_block = mine_new_block(
+            &blockchain[1],
+            transactions,
+            1,
+            next_timestamp(&blockchain),
+            &mut rx,
+        )
+        .await
+        .unwrap();
+        assert!(grpc_client
+            .update_blockchain(Request::new(UpdateBlockchainRequest {
+                blocks: vec![bad_block],
+                node: None,
+            }))
+            .await
+            .is_err());
+    }
+    for task in tasks {
+        task.abort();
+    }
+}
+
+#[test]
+fn test_subsidy_halving() {
+    let reward = RewardSchedule::default();
+    assert_eq!(reward.subsidy(0), 50);
+    assert_eq!(reward.subsidy(999), 50);
+    assert_eq!(reward.subsidy(1000), 25);
+    assert_eq!(reward.subsidy(2000), 12);
+    assert_eq!(reward.subsidy(100_000), 0);
+}
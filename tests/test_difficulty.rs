@@ -0,0 +1,78 @@
+use blockchain::models::blockchain::{
+    mine_new_block, next_timestamp, DifficultySchedule, RewardSchedule,
+};
+use blockchain::node::node_message_client::NodeMessageClient;
+use blockchain::node::Block;
+use blockchain::node::Transaction;
+use blockchain::node::UpdateBlockchainRequest;
+use blockchain::start;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tonic::Request;
+
+/// Create a chain of blocks mined every `interval` seconds at the given difficulty
+fn chain_with_interval(len: u32, interval: u32, difficulty: i32) -> Vec<Block> {
+    (0..len)
+        .map(|i| Block {
+            id: i as i32,
+            timestamp: 1_000_000 + i * interval,
+            difficulty,
+            ..Block::default()
+        })
+        .collect()
+}
+
+#[test]
+fn test_difficulty_retarget() {
+    let schedule = DifficultySchedule::default();
+    assert_eq!(schedule.next_difficulty(&[]), 2);
+
+    // the difficulty only changes every retarget interval
+    assert_eq!(schedule.next_difficulty(&chain_with_interval(9, 1, 3)), 3);
+
+    // blocks mined too fast raise the difficulty, and too slow lower it
+    assert_eq!(schedule.next_difficulty(&chain_with_interval(10, 1, 3)), 4);
+    assert_eq!(schedule.next_difficulty(&chain_with_interval(10, 10, 3)), 3);
+    assert_eq!(
+        schedule.next_difficulty(&chain_with_interval(10, 100, 3)),
+        2
+    );
+    assert_eq!(
+        schedule.next_difficulty(&chain_with_interval(10, 100, 1)),
+        1
+    );
+}
+
+#[tokio::test]
+async fn test_easier_difficulty_rejected() {
+    let task = tokio::spawn(start(50000, None));
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    // mine a first block at an easier difficulty than the initial one
+    let last_block = Block {
+        id: -1,
+        ..Block::default()
+    };
+    let transactions = vec![Transaction::coinbase(
+        "miner",
+        0,
+        &RewardSchedule::default(),
+        &[],
+    )];
+    let (_tx, mut rx) = mpsc::channel::<bool>(1);
+    let block = mine_new_block(&last_block, transactions, 1, next_timestamp(&[]), &mut rx)
+        .await
+        .unwrap();
+
+    let mut grpc_client = NodeMessageClient::connect("http://127.0.0.1:50000")
+        .await
+        .expect("Failed to connect to node");
+    assert!(grpc_client
+        .update_blockchain(Request::new(UpdateBlockchainRequest {
+            blocks: vec![block],
+            node: None,
+        }))
+        .await
+        .is_err());
+    task.abort();
+}
@@ -3,7 +3,7 @@ use blockchain::node::node_message_client::NodeMessageClient;
 use blockchain::node::GenerateTransactionRequest;
 use blockchain::node::GetBlockchainRequest;
 use blockchain::node::UpdateTransactionRequest;
-use blockchain::start;
+use blockchain::start_with_config;
 use std::time::Duration;
 use tonic::Request;
 use uuid::Uuid;
@@ -11,21 +11,32 @@ use uuid::Uuid;
 async fn test_fork_30_percents() {
     let mut tasks = Vec::new();
 
+    // Create a client and create public key and private key
+    let client = common::Client::new();
+
     // start two nodes without connecting them, so they will have different blockchains
     let nodes = vec![50000, 50001, 50002, 50003];
-    tasks.push(tokio::spawn(start(nodes[0], None)));
+    tasks.push(tokio::spawn(start_with_config(common::funded_config(
+        nodes[0], None, &client,
+    ))));
     tokio::time::sleep(Duration::from_millis(200)).await;
-    tasks.push(tokio::spawn(start(nodes[1], None)));
+    tasks.push(tokio::spawn(start_with_config(common::funded_config(
+        nodes[1], None, &client,
+    ))));
     tokio::time::sleep(Duration::from_millis(200)).await;
-    tasks.push(tokio::spawn(start(nodes[2], None)));
+    tasks.push(tokio::spawn(start_with_config(common::funded_config(
+        nodes[2], None, &client,
+    ))));
     tokio::time::sleep(Duration::from_millis(200)).await;
-    tasks.push(tokio::spawn(start(nodes[3], Some(nodes[2]))));
+    tasks.push(tokio::spawn(start_with_config(common::funded_config(
+        nodes[3],
+        Some(nodes[2]),
+        &client,
+    ))));
     tokio::time::sleep(Duration::from_millis(200)).await;
 
     println!("{:?}", nodes);
 
-    // Create a client and create public key and private key
-    let client = common::Client::new();
     let mut grpc_client = NodeMessageClient::connect(format!("http://127.0.0.1:{}", nodes[0]))
         .await
         .expect("Failed to connect to node");
@@ -36,6 +47,7 @@ async fn test_fork_30_percents() {
         receiver: "receiver".to_string(),
         amount: 100,
         fee: 1,
+        nonce: None,
     }));
 
     let tx1 = res.await.unwrap().into_inner().transaction.unwrap();
@@ -46,6 +58,7 @@ async fn test_fork_30_percents() {
         receiver: "receiver2".to_string(),
         amount: 200,
         fee: 2,
+        nonce: None,
     }));
 
     let tx2 = res.await.unwrap().into_inner().transaction.unwrap();
@@ -95,6 +108,7 @@ async fn test_fork_30_percents() {
     grpc_client
         .update_blockchain(Request::new(blockchain::node::UpdateBlockchainRequest {
             blocks: bc1.clone(),
+            node: None,
         }))
         .await
         .unwrap();
@@ -106,6 +120,7 @@ async fn test_fork_30_percents() {
     grpc_client
         .update_blockchain(Request::new(blockchain::node::UpdateBlockchainRequest {
             blocks: bc2.clone(),
+            node: None,
         }))
         .await
         .unwrap();
@@ -118,6 +133,7 @@ async fn test_fork_30_percents() {
         receiver: "receiver3".to_string(),
         amount: 50,
         fee: 2,
+        nonce: None,
     }));
 
     let tx3 = res.await.unwrap().into_inner().transaction.unwrap();
@@ -4,7 +4,7 @@ use blockchain::node::GenerateTransactionRequest;
 use blockchain::node::GetBlockchainRequest;
 use blockchain::node::UpdateBlockchainRequest;
 use blockchain::node::UpdateTransactionRequest;
-use blockchain::start;
+use blockchain::start_with_config;
 use std::time::Duration;
 use tonic::Request;
 use uuid::Uuid;
@@ -12,17 +12,28 @@ use uuid::Uuid;
 async fn test_invalid_block_15_percents() {
     let mut tasks = Vec::new();
 
+    // Create a client and create public key and private key
+    let client = common::Client::new();
+
     let nodes = vec![50000, 50001, 50002];
-    tasks.push(tokio::spawn(start(nodes[0], None)));
+    tasks.push(tokio::spawn(start_with_config(common::funded_config(
+        nodes[0], None, &client,
+    ))));
     tokio::time::sleep(Duration::from_millis(200)).await;
-    tasks.push(tokio::spawn(start(nodes[1], Some(nodes[0]))));
+    tasks.push(tokio::spawn(start_with_config(common::funded_config(
+        nodes[1],
+        Some(nodes[0]),
+        &client,
+    ))));
     tokio::time::sleep(Duration::from_millis(200)).await;
-    tasks.push(tokio::spawn(start(nodes[2], Some(nodes[1]))));
+    tasks.push(tokio::spawn(start_with_config(common::funded_config(
+        nodes[2],
+        Some(nodes[1]),
+        &client,
+    ))));
     tokio::time::sleep(Duration::from_millis(200)).await;
     // Wait for the nodes to start
 
-    // Create a client and create public key and private key
-    let client = common::Client::new();
     let mut grpc_client = NodeMessageClient::connect(format!("http://127.0.0.1:{}", nodes[0]))
         .await
         .expect("Failed to connect to node");
@@ -33,6 +44,7 @@ async fn test_invalid_block_15_percents() {
         receiver: "receiver".to_string(),
         amount: 100,
         fee: 1,
+        nonce: None,
     }));
 
     let transaction = res.await.unwrap().into_inner().transaction.unwrap();
@@ -58,10 +70,10 @@ async fn test_invalid_block_15_percents() {
     let blockchain = res.into_inner().chain;
 
     // create a bad block
-    let mut bad_block = blockchain[0].clone();
-    bad_block.id = 1;
+    let mut bad_block = blockchain[1].clone();
+    bad_block.id = 2;
     bad_block.prev_hash = bad_block.hash.clone();
-    bad_block.transactions[0].amount = 1000;
+    bad_block.transactions[1].amount = 1000;
 
     let mut grpc_client = NodeMessageClient::connect(format!("http://127.0.0.1:{}", nodes[1]))
         .await
@@ -70,6 +82,7 @@ async fn test_invalid_block_15_percents() {
     assert!(grpc_client
         .update_blockchain(Request::new(UpdateBlockchainRequest {
             blocks: vec![bad_block.clone()],
+            node: None,
         }))
         .await
         .is_err());
@@ -80,6 +93,7 @@ async fn test_invalid_block_15_percents() {
     assert!(grpc_client
         .update_blockchain(Request::new(UpdateBlockchainRequest {
             blocks: vec![bad_block.clone()],
+            node: None,
         }))
         .await
         .is_err());
@@ -94,8 +108,8 @@ async fn test_invalid_block_15_percents() {
             .await
             .unwrap();
         let blockchain = res.into_inner().chain;
-        assert_eq!(blockchain.len(), 1);
-        assert_eq!(blockchain[0].transactions[0], transaction);
+        assert_eq!(blockchain.len(), 2);
+        assert_eq!(blockchain[1].transactions[1], transaction);
     }
     for task in tasks {
         task.abort();
@@ -0,0 +1,94 @@
+mod common;
+use blockchain::models::chain_params::ChainParams;
+use blockchain::models::light_client::LightClient;
+use blockchain::node::node_message_client::NodeMessageClient;
+use blockchain::node::GenerateBlocksRequest;
+use blockchain::node::GenerateTransactionRequest;
+use blockchain::node::GetBlockchainRequest;
+use blockchain::node::UpdateTransactionRequest;
+use blockchain::start_with_config;
+use std::time::Duration;
+use tonic::{Code, Request};
+use uuid::Uuid;
+
+#[tokio::test]
+async fn test_light_client() {
+    let client = common::Client::new();
+    let task = tokio::spawn(start_with_config(common::regtest_config(
+        50000, None, &client,
+    )));
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    let mut grpc_client = NodeMessageClient::connect("http://127.0.0.1:50000")
+        .await
+        .expect("Failed to connect to node");
+    let transaction = grpc_client
+        .generate_transaction(Request::new(GenerateTransactionRequest {
+            id: Uuid::new_v4().to_string(),
+            sender: client.public_key.clone(),
+            private_key: client.private_key.clone(),
+            receiver: "receiver".to_string(),
+            amount: 100,
+            fee: 1,
+            nonce: None,
+        }))
+        .await
+        .unwrap()
+        .into_inner()
+        .transaction
+        .unwrap();
+    grpc_client
+        .update_client_transaction(Request::new(UpdateTransactionRequest {
+            transactions: vec![transaction.clone()],
+        }))
+        .await
+        .unwrap();
+
+    // the transaction is pooled when the request returns, so the block with it is mined right away
+    grpc_client
+        .generate_blocks(Request::new(GenerateBlocksRequest {
+            count: 1,
+            miner_address: String::new(),
+        }))
+        .await
+        .unwrap();
+
+    // the light client only keeps the headers of the blockchain
+    let mut light_client =
+        LightClient::connect("http://127.0.0.1:50000".to_string(), ChainParams::regtest())
+            .await
+            .expect("Failed to connect to node");
+    assert!(light_client.sync_headers().await.unwrap());
+    let chain = grpc_client
+        .get_blockchain(Request::new(GetBlockchainRequest {}))
+        .await
+        .unwrap()
+        .into_inner()
+        .chain;
+    assert_eq!(light_client.headers.len(), 2);
+    assert_eq!(
+        light_client.headers[0],
+        ChainParams::regtest().genesis.header()
+    );
+    assert_eq!(light_client.headers[1], chain[1].header());
+
+    // the transaction is confirmed with a merkle proof against the synced headers
+    assert_eq!(
+        light_client
+            .verify_transaction(&transaction.hash)
+            .await
+            .unwrap(),
+        Some(1)
+    );
+
+    // the proofs are looked up by transaction hash, the id chosen by the sender does not find the transaction
+    let status = light_client
+        .verify_transaction(&transaction.id)
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::NotFound);
+
+    // nothing changes when the peer has no new blocks
+    assert!(!light_client.sync_headers().await.unwrap());
+    task.abort();
+}
@@ -1,9 +1,10 @@
 mod common;
 use blockchain::node::node_message_client::NodeMessageClient;
+use blockchain::node::GenerateBlocksRequest;
 use blockchain::node::GenerateTransactionRequest;
 use blockchain::node::GetBlockchainRequest;
 use blockchain::node::UpdateTransactionRequest;
-use blockchain::start;
+use blockchain::start_with_config;
 use std::time::Duration;
 use tonic::Request;
 use uuid::Uuid;
@@ -12,19 +13,27 @@ use uuid::Uuid;
 async fn test_longer_chain_20_percents() {
     let mut tasks = Vec::new();
 
+    // Create a client and create public key and private key
+    let client = common::Client::new();
+
     // start the first two nodes
     let nodes = vec![50000, 50001, 50002];
-    tasks.push(tokio::spawn(start(nodes[0], None)));
+    tasks.push(tokio::spawn(start_with_config(common::regtest_config(
+        nodes[0], None, &client,
+    ))));
     tokio::time::sleep(Duration::from_millis(200)).await;
-    tasks.push(tokio::spawn(start(nodes[1], Some(nodes[0]))));
+    tasks.push(tokio::spawn(start_with_config(common::regtest_config(
+        nodes[1],
+        Some(nodes[0]),
+        &client,
+    ))));
     tokio::time::sleep(Duration::from_millis(200)).await;
     // Wait for the nodes to start
 
-    // Create a client and create public key and private key
-    let client = common::Client::new();
     let mut grpc_client = NodeMessageClient::connect(format!("http://127.0.0.1:{}", nodes[0]))
         .await
         .expect("Failed to connect to node");
+    // the transactions are generated before any of them is pooled, so they are given their nonces
     let tx1 = grpc_client
         .generate_transaction(Request::new(GenerateTransactionRequest {
             id: Uuid::new_v4().to_string(),
@@ -33,6 +42,7 @@ async fn test_longer_chain_20_percents() {
             receiver: "receiver1".to_string(),
             amount: 100,
             fee: 1,
+            nonce: Some(0),
         }))
         .await
         .unwrap()
@@ -48,6 +58,7 @@ async fn test_longer_chain_20_percents() {
             receiver: "receiver2".to_string(),
             amount: 200,
             fee: 1,
+            nonce: Some(1),
         }))
         .await
         .unwrap()
@@ -55,6 +66,8 @@ async fn test_longer_chain_20_percents() {
         .transaction
         .unwrap();
 
+    // the timestamps of the transactions of a block must strictly increase, so tx3 is generated a second after tx2
+    tokio::time::sleep(Duration::from_secs(1)).await;
     let tx3 = grpc_client
         .generate_transaction(Request::new(GenerateTransactionRequest {
             id: Uuid::new_v4().to_string(),
@@ -63,6 +76,7 @@ async fn test_longer_chain_20_percents() {
             receiver: "receiver3".to_string(),
             amount: 50,
             fee: 2,
+            nonce: Some(2),
         }))
         .await
         .unwrap()
@@ -70,30 +84,49 @@ async fn test_longer_chain_20_percents() {
         .transaction
         .unwrap();
 
-    // send a transaction to the nodes, so that they can start mining
+    // send a transaction to the nodes, and mine the first block with it
     grpc_client
         .update_client_transaction(Request::new(UpdateTransactionRequest {
             transactions: vec![tx1.clone()],
         }))
         .await
         .unwrap();
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    grpc_client
+        .generate_blocks(Request::new(GenerateBlocksRequest {
+            count: 1,
+            miner_address: String::new(),
+        }))
+        .await
+        .unwrap();
 
-    // start third node after the first two nodes have start mining
-    tasks.push(tokio::spawn(start(nodes[2], Some(nodes[1]))));
+    // start third node after the first two nodes have mined the first block
+    tasks.push(tokio::spawn(start_with_config(common::regtest_config(
+        nodes[2],
+        Some(nodes[1]),
+        &client,
+    ))));
     tokio::time::sleep(Duration::from_millis(200)).await;
 
-    // send two more transactions to the nodes, the third node should start mining block with tx2 and tx3
+    // send two more transactions to the nodes, the third node mines the block with tx2 and tx3 on the chain it synced
     grpc_client
         .update_client_transaction(Request::new(UpdateTransactionRequest {
             transactions: vec![tx2.clone(), tx3.clone()],
         }))
         .await
         .unwrap();
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    NodeMessageClient::connect(format!("http://127.0.0.1:{}", nodes[2]))
+        .await
+        .expect("Failed to connect to node")
+        .generate_blocks(Request::new(GenerateBlocksRequest {
+            count: 1,
+            miner_address: String::new(),
+        }))
+        .await
+        .unwrap();
 
-    // wait for the transaction to be sent to the blockchain
-    tokio::time::sleep(Duration::from_secs(5)).await;
-
-    // eventually the third node should have a chain with 2 blocks
+    // every node should have a chain with 3 blocks
     for node in &nodes {
         let mut grpc_client = NodeMessageClient::connect(format!("http://127.0.0.1:{}", node))
             .await
@@ -103,10 +136,10 @@ async fn test_longer_chain_20_percents() {
             .await
             .unwrap();
         let blockchain = res.into_inner().chain;
-        assert_eq!(blockchain.len(), 2);
-        assert_eq!(blockchain[0].transactions[0], tx1);
-        assert_eq!(blockchain[1].transactions[0], tx2);
-        assert_eq!(blockchain[1].transactions[1], tx3);
+        assert_eq!(blockchain.len(), 3);
+        assert_eq!(blockchain[1].transactions[1], tx1);
+        assert_eq!(blockchain[2].transactions[1], tx2);
+        assert_eq!(blockchain[2].transactions[2], tx3);
     }
     for task in tasks {
         task.abort();
@@ -0,0 +1,221 @@
+mod common;
+use blockchain::models::chain_params::ChainParams;
+use blockchain::models::ledger::Ledger;
+use blockchain::models::mempool::{Mempool, RejectReason, MAX_MEMPOOL_AGE};
+use blockchain::node::node_message_client::NodeMessageClient;
+use blockchain::node::{
+    Block, GenerateTransactionRequest, GetTransactionListRequest, Transaction,
+    UpdateTransactionRequest,
+};
+use blockchain::start_with_config;
+use std::time::Duration;
+use tonic::Request;
+use uuid::Uuid;
+
+#[test]
+fn test_mempool_indexes() {
+    let mut ledger = Ledger::new(&[("alice".to_string(), 100), ("bob".to_string(), 100)]);
+    let mut mempool = Mempool::default();
+    let b1 = common::transaction("b1", "bob", 0, 10, 1);
+    let a1 = common::transaction("a1", "alice", 0, 10, 1);
+    let a2 = common::transaction("a2", "alice", 1, 10, 1);
+    for tx in [&b1, &a1, &a2] {
+        assert_eq!(mempool.insert(tx.clone(), &ledger), Ok(None));
+    }
+    assert_eq!(mempool.len(), 3);
+    assert_eq!(
+        mempool.size(),
+        common::size(&a1) + common::size(&a2) + common::size(&b1)
+    );
+    assert_eq!(mempool.get("a1"), Some(&a1));
+    assert!(mempool.contains(&b1));
+    // the pool keeps the arrival order, and the transactions of a sender in nonce order
+    assert_eq!(mempool.to_vec(), [b1.clone(), a1.clone(), a2.clone()]);
+    assert_eq!(mempool.sender_transactions("alice"), [&a1, &a2]);
+    assert_eq!(mempool.next_nonce("alice", &ledger), 2);
+
+    // the same hash, or the id of another pending transaction, is a duplicate
+    assert_eq!(
+        mempool.insert(a1.clone(), &ledger),
+        Err(RejectReason::Duplicate)
+    );
+    assert_eq!(
+        mempool.insert(common::transaction("a1", "bob", 1, 1, 1), &ledger),
+        Err(RejectReason::Duplicate)
+    );
+    // the nonces of a sender follow each other without a gap
+    assert_eq!(
+        mempool.insert(common::transaction("a4", "alice", 3, 1, 1), &ledger),
+        Err(RejectReason::NonceGap)
+    );
+    // alice has 78 left after her pending transactions
+    assert_eq!(
+        mempool.insert(common::transaction("a3", "alice", 2, 78, 1), &ledger),
+        Err(RejectReason::InsufficientBalance)
+    );
+    assert_eq!(
+        mempool.insert(common::transaction("a3", "alice", 2, 77, 1), &ledger),
+        Ok(None)
+    );
+
+    // the transactions of a block leave the pool, and their nonces cannot be used again
+    let block = Block {
+        transactions: vec![a1.clone(), b1.clone()],
+        ..Block::default()
+    };
+    assert!(ledger.apply_block(&block));
+    mempool.remove_included(&block.transactions);
+    assert_eq!(mempool.len(), 2);
+    assert_eq!(mempool.get("a1"), None);
+    assert!(mempool.sender_transactions("bob").is_empty());
+    assert_eq!(
+        mempool.size(),
+        common::size(&a2) + common::size(&mempool.to_vec()[1])
+    );
+    assert_eq!(
+        mempool.insert(common::transaction("b2", "bob", 0, 10, 2), &ledger),
+        Err(RejectReason::NonceTooLow)
+    );
+    assert_eq!(mempool.next_nonce("bob", &ledger), 1);
+}
+
+#[test]
+fn test_mempool_eviction() {
+    let accounts = ["alice", "bobby", "carol", "david"];
+    let allocations: Vec<(String, i64)> = accounts
+        .iter()
+        .map(|account| (account.to_string(), 100))
+        .collect();
+    let ledger = Ledger::new(&allocations);
+    let transactions: Vec<Transaction> = accounts
+        .iter()
+        .zip([2, 1, 3, 1])
+        .enumerate()
+        .map(|(i, (sender, fee))| common::transaction(&i.to_string(), sender, 0, 10, fee))
+        .collect();
+    // the transactions have the same size, and the pool holds two of them
+    let mut mempool = Mempool::new(2 * common::size(&transactions[0]), MAX_MEMPOOL_AGE);
+    assert_eq!(mempool.insert(transactions[0].clone(), &ledger), Ok(None));
+    assert_eq!(mempool.insert(transactions[1].clone(), &ledger), Ok(None));
+
+    // a transaction paying more evicts the one paying the least
+    assert_eq!(mempool.insert(transactions[2].clone(), &ledger), Ok(None));
+    assert_eq!(
+        mempool.to_vec(),
+        [transactions[0].clone(), transactions[2].clone()]
+    );
+
+    // a transaction paying no more than the pooled ones is refused
+    assert_eq!(
+        mempool.insert(transactions[3].clone(), &ledger),
+        Err(RejectReason::PoolFull)
+    );
+    assert_eq!(mempool.len(), 2);
+}
+
+#[test]
+fn test_mempool_expiry() {
+    let ledger = Ledger::new(&[("alice".to_string(), 100), ("bob".to_string(), 100)]);
+    let mut mempool = Mempool::new(usize::MAX, Duration::from_millis(200));
+    let first = common::transaction("first", "alice", 0, 10, 1);
+    assert_eq!(mempool.insert(first, &ledger), Ok(None));
+    std::thread::sleep(Duration::from_millis(250));
+    let second = common::transaction("second", "bob", 0, 10, 1);
+    assert_eq!(mempool.insert(second.clone(), &ledger), Ok(None));
+
+    // the expired transaction is only dropped when the pool is expired, before mining
+    assert_eq!(mempool.len(), 2);
+    assert_eq!(mempool.expire(), 1);
+    assert_eq!(mempool.to_vec(), [second]);
+    std::thread::sleep(Duration::from_millis(250));
+    assert_eq!(mempool.expire(), 1);
+    assert!(mempool.is_empty());
+    assert_eq!(mempool.size(), 0);
+
+    // the later transaction of a sender is dropped with the expired one it depends on
+    for (nonce, id) in ["third", "fourth"].into_iter().enumerate() {
+        let transaction = common::transaction(id, "alice", nonce as u64, 10, 1);
+        assert_eq!(mempool.insert(transaction, &ledger), Ok(None));
+        std::thread::sleep(Duration::from_millis(120));
+    }
+    assert_eq!(mempool.expire(), 2);
+    assert!(mempool.is_empty());
+}
+
+#[tokio::test]
+async fn test_rejection_reasons() {
+    let client = common::Client::new();
+    let mut config = common::funded_config(50000, None, &client);
+    config.params = ChainParams::regtest();
+    let task = tokio::spawn(start_with_config(config));
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    let mut grpc_client = NodeMessageClient::connect("http://127.0.0.1:50000")
+        .await
+        .expect("Failed to connect to node");
+    let mut transactions = Vec::new();
+    for (nonce, amount) in [600, 500].into_iter().enumerate() {
+        let transaction = grpc_client
+            .generate_transaction(Request::new(GenerateTransactionRequest {
+                id: Uuid::new_v4().to_string(),
+                sender: client.public_key.clone(),
+                private_key: client.private_key.clone(),
+                receiver: "receiver".to_string(),
+                amount,
+                fee: 1,
+                nonce: Some(nonce as u64),
+            }))
+            .await
+            .unwrap()
+            .into_inner()
+            .transaction
+            .unwrap();
+        transactions.push(transaction);
+    }
+    let mut tampered = transactions[0].clone();
+    tampered.id = Uuid::new_v4().to_string();
+    tampered.amount = 1;
+
+    let res = grpc_client
+        .update_client_transaction(Request::new(UpdateTransactionRequest {
+            transactions: vec![
+                transactions[0].clone(),
+                tampered.clone(),
+                transactions[1].clone(),
+                transactions[0].clone(),
+            ],
+        }))
+        .await
+        .unwrap()
+        .into_inner();
+    assert!(!res.success);
+    let rejected: Vec<(String, String)> = res
+        .rejected
+        .into_iter()
+        .map(|rejected| (rejected.id, rejected.reason))
+        .collect();
+    assert_eq!(
+        rejected,
+        [
+            (tampered.id, RejectReason::Invalid.to_string()),
+            (
+                transactions[1].id.clone(),
+                RejectReason::InsufficientBalance.to_string()
+            ),
+            (
+                transactions[0].id.clone(),
+                RejectReason::Duplicate.to_string()
+            ),
+        ]
+    );
+
+    // only the accepted transaction is pooled
+    let pool = grpc_client
+        .get_transaction_list(Request::new(GetTransactionListRequest {}))
+        .await
+        .unwrap()
+        .into_inner()
+        .transactions;
+    assert_eq!(pool, transactions[..1]);
+    task.abort();
+}
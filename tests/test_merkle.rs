@@ -0,0 +1,42 @@
+use blockchain::models::blockchain::{mine_new_block, next_timestamp, sha_hash};
+use blockchain::models::chain_params::ChainParams;
+use blockchain::models::merkle::merkle_root;
+use blockchain::node::Transaction;
+use tokio::sync::mpsc;
+
+#[test]
+fn test_merkle_root() {
+    let hashes: Vec<String> = ["a", "b", "c"].iter().map(|data| sha_hash(data)).collect();
+    assert_eq!(merkle_root(&[]), "");
+    assert_eq!(merkle_root(&hashes[..1]), hashes[0]);
+
+    let ab = sha_hash(&format!("{}{}", hashes[0], hashes[1]));
+    assert_eq!(merkle_root(&hashes[..2]), ab);
+
+    // the last hash is paired with itself on odd levels
+    let cc = sha_hash(&format!("{}{}", hashes[2], hashes[2]));
+    assert_eq!(merkle_root(&hashes), sha_hash(&format!("{}{}", ab, cc)));
+}
+
+#[tokio::test]
+async fn test_merkle_root_in_header() {
+    let params = ChainParams::default();
+    let reward = &params.reward;
+    let transactions = vec![Transaction::coinbase("miner", 1, reward, &[])];
+    let (_tx, mut rx) = mpsc::channel::<bool>(1);
+    let timestamp = next_timestamp(std::slice::from_ref(&params.genesis));
+    let block = mine_new_block(&params.genesis, transactions, 2, timestamp, &mut rx)
+        .await
+        .unwrap();
+    assert_eq!(block.merkle_root, block.compute_merkle_root());
+    assert!(block.check_block_validity(&params));
+
+    // a transaction replaced after mining no longer matches the merkle root in the header
+    let mut tampered = block.clone();
+    tampered.transactions[0] = Transaction::coinbase("attacker", 1, reward, &[]);
+    assert!(!tampered.check_block_validity(&params));
+
+    // and a merkle root replaced after mining no longer matches the proof of work
+    tampered.merkle_root = tampered.compute_merkle_root();
+    assert!(!tampered.check_block_validity(&params));
+}
@@ -1,9 +1,10 @@
 mod common;
 use blockchain::node::node_message_client::NodeMessageClient;
+use blockchain::node::GenerateBlocksRequest;
 use blockchain::node::GenerateTransactionRequest;
 use blockchain::node::GetBlockchainRequest;
 use blockchain::node::UpdateTransactionRequest;
-use blockchain::start;
+use blockchain::start_with_config;
 use std::time::Duration;
 use tonic::Request;
 use uuid::Uuid;
@@ -12,17 +13,28 @@ use uuid::Uuid;
 async fn test_mine_block_15_percents() {
     let mut tasks = Vec::new();
 
+    // Create a client and create public key and private key
+    let client = common::Client::new();
+
     let nodes = vec![50000, 50001, 50002];
-    tasks.push(tokio::spawn(start(nodes[0], None)));
+    tasks.push(tokio::spawn(start_with_config(common::regtest_config(
+        nodes[0], None, &client,
+    ))));
     tokio::time::sleep(Duration::from_millis(200)).await;
-    tasks.push(tokio::spawn(start(nodes[1], Some(nodes[0]))));
+    tasks.push(tokio::spawn(start_with_config(common::regtest_config(
+        nodes[1],
+        Some(nodes[0]),
+        &client,
+    ))));
     tokio::time::sleep(Duration::from_millis(200)).await;
-    tasks.push(tokio::spawn(start(nodes[2], Some(nodes[1]))));
+    tasks.push(tokio::spawn(start_with_config(common::regtest_config(
+        nodes[2],
+        Some(nodes[1]),
+        &client,
+    ))));
     tokio::time::sleep(Duration::from_millis(200)).await;
     // Wait for the nodes to start
 
-    // Create a client and create public key and private key
-    let client = common::Client::new();
     let mut grpc_client = NodeMessageClient::connect(format!("http://127.0.0.1:{}", nodes[0]))
         .await
         .expect("Failed to connect to node");
@@ -33,6 +45,7 @@ async fn test_mine_block_15_percents() {
         receiver: "receiver".to_string(),
         amount: 100,
         fee: 1,
+        nonce: None,
     }));
 
     let transaction = res.await.unwrap().into_inner().transaction.unwrap();
@@ -43,8 +56,17 @@ async fn test_mine_block_15_percents() {
         .await
         .unwrap();
 
-    // wait for the transaction to be sent to the blockchain
-    tokio::time::sleep(Duration::from_secs(5)).await;
+    // wait for the transaction to be relayed to the peers
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    // the block is sent to every node before the request returns
+    grpc_client
+        .generate_blocks(Request::new(GenerateBlocksRequest {
+            count: 1,
+            miner_address: String::new(),
+        }))
+        .await
+        .unwrap();
 
     for node in &nodes {
         let mut grpc_client = NodeMessageClient::connect(format!("http://127.0.0.1:{}", node))
@@ -55,8 +77,8 @@ async fn test_mine_block_15_percents() {
             .await
             .unwrap();
         let blockchain = res.into_inner().chain;
-        assert_eq!(blockchain.len(), 1);
-        assert_eq!(blockchain[0].transactions[0], transaction);
+        assert_eq!(blockchain.len(), 2);
+        assert_eq!(blockchain[1].transactions[1], transaction);
     }
     for task in tasks {
         task.abort();
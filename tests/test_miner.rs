@@ -0,0 +1,127 @@
+mod common;
+use blockchain::models::miner::{CpuMiner, Miner, MiningBackend, MiningStats, HASHRATE_WINDOWS};
+use blockchain::node::Block;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+
+#[test]
+fn test_cpu_miner() {
+    let block = common::template(3);
+    for threads in [1, 4] {
+        let cancel = AtomicBool::new(false);
+        let hashes = AtomicU64::new(0);
+        let (nonce, hash) = CpuMiner::new(threads)
+            .mine(&block, &cancel, &hashes)
+            .unwrap();
+        assert!(hashes.load(Ordering::Relaxed) > 0);
+        let mut mined = block.clone();
+        mined.nonce = nonce;
+        mined.hash = hash;
+        assert!(mined.header().check_header_validity());
+    }
+
+    // a single thread finds the lowest winning nonce, like the nonces are tried in order
+    let cancel = AtomicBool::new(false);
+    let hashes = AtomicU64::new(0);
+    let (nonce, _) = CpuMiner::new(1).mine(&block, &cancel, &hashes).unwrap();
+    // and counts every nonce it tried
+    assert_eq!(hashes.load(Ordering::Relaxed), nonce as u64 + 1);
+    let mut lower = block.clone();
+    for n in 0..nonce {
+        lower.nonce = n;
+        lower.hash = lower.compute_hash(n);
+        assert!(!lower.header().check_header_validity());
+    }
+}
+
+#[test]
+fn test_cpu_miner_cancel() {
+    // no hash has 64 leading zeros, so the miner only stops when cancelled
+    let block = common::template(64);
+    let cancel = Arc::new(AtomicBool::new(false));
+    let canceller = {
+        let cancel = cancel.clone();
+        std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(100));
+            cancel.store(true, Ordering::Relaxed);
+        })
+    };
+    let start = Instant::now();
+    let hashes = AtomicU64::new(0);
+    assert!(CpuMiner::new(2).mine(&block, &cancel, &hashes).is_none());
+    assert!(hashes.load(Ordering::Relaxed) > 0);
+    assert!(start.elapsed() < Duration::from_secs(5));
+    canceller.join().unwrap();
+}
+
+/// A backend that always reports the same nonce, to check that the mined block uses the backend result
+struct FixedNonce;
+
+impl MiningBackend for FixedNonce {
+    fn mine(&self, block: &Block, _: &AtomicBool, hashes: &AtomicU64) -> Option<(i32, String)> {
+        hashes.fetch_add(43, Ordering::Relaxed);
+        Some((42, block.compute_hash(42)))
+    }
+}
+
+#[tokio::test]
+async fn test_mine_block() {
+    let (tx, mut rx) = mpsc::channel::<bool>(1);
+    let block = common::template(0);
+    let miner = Miner::new(Arc::new(FixedNonce));
+    let mined = miner.mine_block(block.clone(), &mut rx).await.unwrap();
+    assert_eq!(mined.nonce, 42);
+    assert_eq!(mined.hash, block.compute_hash(42));
+    let info = miner.stats.info();
+    assert_eq!(info.hashes, 43);
+    assert_eq!(info.blocks_mined, 1);
+    assert_eq!(info.interrupted, 0);
+    assert!(!info.mining);
+
+    // a stop signal cancels the mining, and the mining threads stop
+    let stop = tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        tx.send(true).await.unwrap();
+    });
+    let start = Instant::now();
+    let miner = Miner::new(Arc::new(CpuMiner::new(2)));
+    let result = miner.mine_block(common::template(64), &mut rx).await;
+    assert_eq!(result.unwrap_err().code(), tonic::Code::Cancelled);
+    assert!(start.elapsed() < Duration::from_secs(5));
+    stop.await.unwrap();
+    assert_eq!(miner.stats.blocks_mined(), 0);
+    assert_eq!(miner.stats.interrupted(), 1);
+    assert!(miner.stats.hashes() > 0);
+    assert!(!miner.stats.is_mining());
+}
+
+#[tokio::test]
+async fn test_mining_stats() {
+    let stats = MiningStats::default();
+    for window in HASHRATE_WINDOWS {
+        assert_eq!(stats.hashrate(window), 0.0);
+    }
+
+    // the hashrate covers the hashes since the node started while it runs for less than the window
+    let (_tx, mut rx) = mpsc::channel::<bool>(1);
+    let miner = Miner::new(Arc::new(CpuMiner::new(1)));
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    miner
+        .mine_block(common::template(3), &mut rx)
+        .await
+        .unwrap();
+    miner.stats.record_sample();
+    let hashes = miner.stats.hashes();
+    for window in HASHRATE_WINDOWS {
+        let hashrate = miner.stats.hashrate(window);
+        assert!(hashrate > 0.0);
+        assert!(hashrate < hashes as f64 / 0.1);
+    }
+
+    // no hashes are attempted within a window that starts after the last sample
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(miner.stats.hashrate(Duration::from_millis(50)), 0.0);
+    assert!(miner.stats.since_last_block() >= Duration::from_millis(100));
+}
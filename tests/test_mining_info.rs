@@ -0,0 +1,48 @@
+mod common;
+use blockchain::models::chain_params::ChainParams;
+use blockchain::node::node_message_client::NodeMessageClient;
+use blockchain::node::{GenerateBlocksRequest, GetMiningInfoRequest};
+use blockchain::start_with_config;
+use std::time::Duration;
+use tonic::Request;
+
+#[tokio::test]
+async fn test_get_mining_info() {
+    let client = common::Client::new();
+    let mut config = common::funded_config(50000, None, &client);
+    config.params = ChainParams::regtest();
+    let task = tokio::spawn(start_with_config(config));
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    let mut grpc_client = NodeMessageClient::connect("http://127.0.0.1:50000")
+        .await
+        .expect("Failed to connect to node");
+    let info = grpc_client
+        .get_mining_info(Request::new(GetMiningInfoRequest {}))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(info.hashes, 0);
+    assert_eq!(info.blocks_mined, 0);
+    assert!(!info.mining);
+
+    // the generated blocks are counted with the hashes tried to mine them
+    grpc_client
+        .generate_blocks(Request::new(GenerateBlocksRequest {
+            count: 2,
+            miner_address: String::new(),
+        }))
+        .await
+        .unwrap();
+    let info = grpc_client
+        .get_mining_info(Request::new(GetMiningInfoRequest {}))
+        .await
+        .unwrap()
+        .into_inner();
+    assert!(info.hashes >= 2);
+    assert_eq!(info.blocks_mined, 2);
+    assert_eq!(info.interrupted, 0);
+    assert_eq!(info.seconds_since_last_block, 0);
+    assert!(!info.mining);
+    task.abort();
+}
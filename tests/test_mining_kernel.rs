@@ -0,0 +1,63 @@
+mod common;
+use blockchain::models::blockchain::sha_hash;
+use blockchain::models::cudalib::mine_block_reference;
+use blockchain::models::miner::{CpuMiner, MiningBackend};
+use std::sync::atomic::{AtomicBool, AtomicU64};
+
+#[test]
+fn test_reference_kernel() {
+    // the kernel hashes the data followed by the decimal nonce, like the block header hash
+    let (hash, nonce) = mine_block_reference("data|", 0, 0, 10).unwrap();
+    assert_eq!(nonce, 0);
+    assert_eq!(hash, sha_hash("data|0"));
+
+    // data longer than one sha256 block
+    let data = "x".repeat(100);
+    let (hash, nonce) = mine_block_reference(&data, 3, 0, i32::MAX).unwrap();
+    assert_eq!(hash, sha_hash(&format!("{}{}", data, nonce)));
+    assert!(hash.starts_with("000"));
+    for lower in 0..nonce {
+        assert!(!sha_hash(&format!("{}{}", data, lower)).starts_with("000"));
+    }
+
+    // only the nonces below the maximum are tried
+    assert_eq!(mine_block_reference(&data, 3, 0, nonce), None);
+    assert!(mine_block_reference(&data, 3, 0, nonce + 1).is_some());
+
+    // a range of nonces is searched from its start, so the nonce space can be searched in batches
+    assert_eq!(
+        mine_block_reference(&data, 3, nonce, nonce + 1),
+        Some((hash, nonce))
+    );
+    let (next_hash, next_nonce) = mine_block_reference(&data, 3, nonce + 1, i32::MAX).unwrap();
+    assert!(next_nonce > nonce);
+    assert_eq!(next_hash, sha_hash(&format!("{}{}", data, next_nonce)));
+    assert!(next_hash.starts_with("000"));
+}
+
+#[test]
+fn test_reference_kernel_mines_blocks() {
+    // the kernel finds the same nonce as a single thread CPU miner, which tries the nonces in order
+    let mut block = common::template(3);
+    let (hash, nonce) = mine_block_reference(&block.header_data(), 3, 0, i32::MAX).unwrap();
+    let cancel = AtomicBool::new(false);
+    assert_eq!(
+        CpuMiner::new(1).mine(&block, &cancel, &AtomicU64::new(0)),
+        Some((nonce, hash.clone()))
+    );
+    block.nonce = nonce;
+    block.hash = hash;
+    assert!(block.header().check_header_validity());
+}
+
+#[cfg(feature = "cuda")]
+#[test]
+fn test_gpu_kernel() {
+    use blockchain::models::cudalib::mine_block_gpu;
+    let block = common::template(4);
+    let data = block.header_data();
+    assert_eq!(
+        mine_block_gpu(&data, 4, 0, i32::MAX).unwrap(),
+        mine_block_reference(&data, 4, 0, i32::MAX)
+    );
+}
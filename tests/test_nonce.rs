@@ -0,0 +1,174 @@
+mod common;
+use blockchain::models::blockchain::{mine_new_block, next_timestamp};
+use blockchain::models::chain_params::ChainParams;
+use blockchain::models::ledger::Ledger;
+use blockchain::models::mempool::RejectReason;
+use blockchain::node::node_message_client::NodeMessageClient;
+use blockchain::node::{
+    Block, GenerateBlocksRequest, GenerateTransactionRequest, GetAccountRequest,
+    GetBlockchainRequest, Transaction, UpdateBlockchainRequest, UpdateTransactionRequest,
+};
+use blockchain::start_with_config;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tonic::Request;
+use uuid::Uuid;
+
+/// Build a transaction of alice, the ledger does not check the signatures
+fn transaction(nonce: u64) -> Transaction {
+    common::transaction_at(&nonce.to_string(), "alice", nonce, 10, 1, 10 + nonce as u32)
+}
+
+/// Build a block with the given transactions
+fn block(transactions: Vec<Transaction>) -> Block {
+    Block {
+        transactions,
+        ..Block::default()
+    }
+}
+
+#[test]
+fn test_ledger_nonces() {
+    let mut ledger = Ledger::new(&[("alice".to_string(), 100)]);
+    assert_eq!(ledger.nonce("alice"), 0);
+
+    // a block cannot skip a nonce, or use one twice
+    assert!(!ledger.apply_block(&block(vec![transaction(1)])));
+    assert!(!ledger.apply_block(&block(vec![transaction(0), transaction(0)])));
+    assert_eq!(ledger.nonce("alice"), 0);
+    assert_eq!(ledger.balance("alice"), 100);
+
+    let applied = block(vec![transaction(0), transaction(1)]);
+    assert!(ledger.apply_block(&applied));
+    assert_eq!(ledger.nonce("alice"), 2);
+    assert_eq!(ledger.nonce("receiver"), 0);
+
+    // a transaction of the chain cannot be replayed in a later block
+    assert!(!ledger.apply_block(&block(vec![transaction(1)])));
+    assert!(ledger.apply_block(&block(vec![transaction(2)])));
+
+    // reverting the blocks frees their nonces again
+    ledger.revert_block(&block(vec![transaction(2)]));
+    ledger.revert_block(&applied);
+    assert_eq!(ledger.nonce("alice"), 0);
+    assert_eq!(ledger.balance("alice"), 100);
+}
+
+#[tokio::test]
+async fn test_transaction_replay() {
+    let client = common::Client::new();
+    let mut config = common::funded_config(50000, None, &client);
+    config.params = ChainParams::regtest();
+    let task = tokio::spawn(start_with_config(config));
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    let mut grpc_client = NodeMessageClient::connect("http://127.0.0.1:50000")
+        .await
+        .expect("Failed to connect to node");
+    // a generated transaction that is never sent does not take the nonce of the next one
+    let mut generated = Vec::new();
+    for _ in 0..2 {
+        let transaction = grpc_client
+            .generate_transaction(Request::new(GenerateTransactionRequest {
+                id: Uuid::new_v4().to_string(),
+                sender: client.public_key.clone(),
+                private_key: client.private_key.clone(),
+                receiver: "receiver".to_string(),
+                amount: 100,
+                fee: 1,
+                nonce: None,
+            }))
+            .await
+            .unwrap()
+            .into_inner()
+            .transaction
+            .unwrap();
+        assert_eq!(transaction.nonce, 0);
+        generated.push(transaction);
+    }
+    let transaction = generated.pop().unwrap();
+    let res = grpc_client
+        .update_client_transaction(Request::new(UpdateTransactionRequest {
+            transactions: vec![transaction.clone()],
+        }))
+        .await
+        .unwrap()
+        .into_inner();
+    assert!(res.success);
+    let account = grpc_client
+        .get_account(Request::new(GetAccountRequest {
+            account: client.public_key.clone(),
+        }))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!((account.nonce, account.pending_nonce), (0, 1));
+
+    grpc_client
+        .generate_blocks(Request::new(GenerateBlocksRequest {
+            count: 1,
+            miner_address: String::new(),
+        }))
+        .await
+        .unwrap();
+    let account = grpc_client
+        .get_account(Request::new(GetAccountRequest {
+            account: client.public_key.clone(),
+        }))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(account.balance, 1000 - 101);
+    assert_eq!((account.nonce, account.pending_nonce), (1, 1));
+
+    // the mined transaction is refused by the pool when it is sent again
+    let res = grpc_client
+        .update_client_transaction(Request::new(UpdateTransactionRequest {
+            transactions: vec![transaction.clone()],
+        }))
+        .await
+        .unwrap()
+        .into_inner();
+    assert!(!res.success);
+    assert_eq!(
+        res.rejected[0].reason,
+        RejectReason::NonceTooLow.to_string()
+    );
+
+    // and a block that replays it is refused
+    let chain = grpc_client
+        .get_blockchain(Request::new(GetBlockchainRequest {}))
+        .await
+        .unwrap()
+        .into_inner()
+        .chain;
+    let params = ChainParams::regtest();
+    let transactions = vec![
+        Transaction::coinbase(
+            "miner",
+            2,
+            &params.reward,
+            std::slice::from_ref(&transaction),
+        ),
+        transaction,
+    ];
+    let (_tx, mut rx) = mpsc::channel::<bool>(1);
+    let replay = mine_new_block(&chain[1], transactions, 1, next_timestamp(&chain), &mut rx)
+        .await
+        .unwrap();
+    assert!(grpc_client
+        .update_blockchain(Request::new(UpdateBlockchainRequest {
+            blocks: vec![replay],
+            node: None,
+        }))
+        .await
+        .is_err());
+    let chain = grpc_client
+        .get_blockchain(Request::new(GetBlockchainRequest {}))
+        .await
+        .unwrap()
+        .into_inner()
+        .chain;
+    assert_eq!(chain.len(), 2);
+    task.abort();
+}
@@ -0,0 +1,131 @@
+mod common;
+use blockchain::models::chain_params::ChainParams;
+use blockchain::models::orphan::OrphanPool;
+use blockchain::node::node_message_client::NodeMessageClient;
+use blockchain::node::{
+    GetBlockchainRequest, GetPeerListRequest, JoinNetworkRequest, NodeInfo, UpdateBlockchainRequest,
+};
+use blockchain::start;
+use std::time::Duration;
+use tonic::{Code, Request};
+
+#[test]
+fn test_orphan_pool_limits() {
+    let chain = common::mine_chain(3, "miner");
+    let mut pool = OrphanPool::new(2, Duration::from_millis(100));
+    for block in &chain[1..] {
+        assert!(pool.add(block.clone()));
+    }
+    assert!(!pool.add(chain[3].clone()));
+
+    // the oldest block is dropped when the pool is full
+    assert_eq!(pool.len(), 2);
+    assert!(!pool.contains(&chain[1].hash));
+    assert!(pool.take_children(&chain[0].hash).is_empty());
+    assert_eq!(pool.take_children(&chain[1].hash), chain[2..]);
+    assert!(pool.is_empty());
+
+    // the blocks are dropped after the maximum age
+    pool.add(chain[1].clone());
+    std::thread::sleep(Duration::from_millis(150));
+    assert!(pool.take_children(&chain[0].hash).is_empty());
+    assert!(pool.is_empty());
+}
+
+#[tokio::test]
+async fn test_orphan_blocks() {
+    let mut tasks = Vec::new();
+    let nodes = [50000, 50001];
+    for node in nodes {
+        tasks.push(tokio::spawn(start(node, None)));
+    }
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    let chain = common::mine_chain(3, "miner");
+    let mut peer = NodeMessageClient::connect(format!("http://127.0.0.1:{}", nodes[1]))
+        .await
+        .expect("Failed to connect to node");
+    let response = peer
+        .update_blockchain(Request::new(UpdateBlockchainRequest {
+            blocks: chain.clone(),
+            node: None,
+        }))
+        .await
+        .unwrap()
+        .into_inner();
+    assert!(response.success);
+
+    // the node only requests the missing blocks from a peer it knows
+    let sender = peer
+        .get_peer_list(Request::new(GetPeerListRequest {}))
+        .await
+        .unwrap()
+        .into_inner()
+        .nodes
+        .pop()
+        .unwrap();
+    let mut grpc_client = NodeMessageClient::connect(format!("http://127.0.0.1:{}", nodes[0]))
+        .await
+        .expect("Failed to connect to node");
+    grpc_client
+        .join_network(Request::new(JoinNetworkRequest {
+            node: Some(sender.clone()),
+            network_magic: ChainParams::default().network_magic,
+            genesis_hash: ChainParams::default().genesis_hash().to_string(),
+        }))
+        .await
+        .unwrap();
+
+    // the last block arrives first, the node asks the sender for the missing blocks
+    let response = grpc_client
+        .update_blockchain(Request::new(UpdateBlockchainRequest {
+            blocks: chain[2..].to_vec(),
+            node: Some(sender),
+        }))
+        .await
+        .unwrap()
+        .into_inner();
+    assert!(!response.success);
+    assert_eq!(response.chain_length, 1);
+
+    // wait for the missing blocks to be fetched and the orphan block to be connected
+    tokio::time::sleep(Duration::from_secs(1)).await;
+    let blockchain = grpc_client
+        .get_blockchain(Request::new(GetBlockchainRequest {}))
+        .await
+        .unwrap()
+        .into_inner()
+        .chain;
+    assert_eq!(blockchain, chain);
+    for task in tasks {
+        task.abort();
+    }
+}
+
+#[tokio::test]
+async fn test_orphan_blocks_refused() {
+    let task = tokio::spawn(start(50002, None));
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    let mut grpc_client = NodeMessageClient::connect("http://127.0.0.1:50002")
+        .await
+        .expect("Failed to connect to node");
+
+    // a block far ahead of the chain would let the sender make the node download without limit
+    let mut block = common::mine_chain(1, "miner").pop().unwrap();
+    for id in [i32::MAX, -1] {
+        block.id = id;
+        let status = grpc_client
+            .update_blockchain(Request::new(UpdateBlockchainRequest {
+                blocks: vec![block.clone()],
+                node: Some(NodeInfo {
+                    id: "peer".to_string(),
+                    ip: "127.0.0.1".to_string(),
+                    port: 50003,
+                }),
+            }))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), Code::InvalidArgument);
+    }
+    task.abort();
+}
@@ -0,0 +1,149 @@
+mod common;
+use blockchain::models::blockchain::{mine_new_block, next_timestamp};
+use blockchain::models::chain_params::ChainParams;
+use blockchain::node::node_message_client::NodeMessageClient;
+use blockchain::node::GenerateBlocksRequest;
+use blockchain::node::GenerateTransactionRequest;
+use blockchain::node::GetBlockchainRequest;
+use blockchain::node::GetTransactionListRequest;
+use blockchain::node::Transaction;
+use blockchain::node::UpdateBlockchainRequest;
+use blockchain::node::UpdateTransactionRequest;
+use blockchain::start_with_config;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tonic::Request;
+use uuid::Uuid;
+
+#[tokio::test]
+async fn test_overspend() {
+    let mut tasks = Vec::new();
+
+    // Create a client that holds a balance of 1000
+    let client = common::Client::new();
+
+    let nodes = vec![50000, 50001];
+    tasks.push(tokio::spawn(start_with_config(common::regtest_config(
+        nodes[0], None, &client,
+    ))));
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    tasks.push(tokio::spawn(start_with_config(common::regtest_config(
+        nodes[1],
+        Some(nodes[0]),
+        &client,
+    ))));
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    let mut grpc_client = NodeMessageClient::connect(format!("http://127.0.0.1:{}", nodes[0]))
+        .await
+        .expect("Failed to connect to node");
+    let mut transactions = Vec::new();
+    for (receiver, amount) in [("receiver1", 600), ("receiver2", 500), ("receiver3", 500)] {
+        let transaction = grpc_client
+            .generate_transaction(Request::new(GenerateTransactionRequest {
+                id: Uuid::new_v4().to_string(),
+                sender: client.public_key.clone(),
+                private_key: client.private_key.clone(),
+                receiver: receiver.to_string(),
+                amount,
+                fee: 1,
+                nonce: None,
+            }))
+            .await
+            .unwrap()
+            .into_inner()
+            .transaction
+            .unwrap();
+        transactions.push(transaction);
+    }
+
+    // the two transactions are affordable on their own, but not together
+    grpc_client
+        .update_client_transaction(Request::new(UpdateTransactionRequest {
+            transactions: transactions[..2].to_vec(),
+        }))
+        .await
+        .unwrap();
+
+    // wait for the transaction to be relayed to the peer, then mine the block with it
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    grpc_client
+        .generate_blocks(Request::new(GenerateBlocksRequest {
+            count: 1,
+            miner_address: String::new(),
+        }))
+        .await
+        .unwrap();
+
+    // only the first transaction is mined, and the second one is not left in the pool
+    for node in &nodes {
+        let mut grpc_client = NodeMessageClient::connect(format!("http://127.0.0.1:{}", node))
+            .await
+            .expect("Failed to connect to node");
+        let blockchain = grpc_client
+            .get_blockchain(Request::new(GetBlockchainRequest {}))
+            .await
+            .unwrap()
+            .into_inner()
+            .chain;
+        assert_eq!(blockchain.len(), 2);
+        assert_eq!(blockchain[1].transactions[1..], transactions[..1]);
+        let pool = grpc_client
+            .get_transaction_list(Request::new(GetTransactionListRequest {}))
+            .await
+            .unwrap()
+            .into_inner()
+            .transactions;
+        assert!(pool.is_empty());
+    }
+
+    // mine a block that spends more than the remaining balance of the client
+    let mut grpc_client = NodeMessageClient::connect(format!("http://127.0.0.1:{}", nodes[1]))
+        .await
+        .expect("Failed to connect to node");
+    let blockchain = grpc_client
+        .get_blockchain(Request::new(GetBlockchainRequest {}))
+        .await
+        .unwrap()
+        .into_inner()
+        .chain;
+    let block_transactions = vec![
+        Transaction::coinbase(
+            "miner",
+            2,
+            &ChainParams::regtest().reward,
+            &transactions[2..],
+        ),
+        transactions[2].clone(),
+    ];
+    let (_tx, mut rx) = mpsc::channel::<bool>(1);
+    let bad_block = mine_new_block(
+        &blockchain[1],
+        block_transactions,
+        1,
+        next_timestamp(&blockchain),
+        &mut rx,
+    )
+    .await
+    .unwrap();
+
+    assert!(grpc_client
+        .update_blockchain(Request::new(UpdateBlockchainRequest {
+            blocks: vec![bad_block],
+            node: None,
+        }))
+        .await
+        .is_err());
+
+    // expect the node to ignore the overspending block
+    let blockchain = grpc_client
+        .get_blockchain(Request::new(GetBlockchainRequest {}))
+        .await
+        .unwrap()
+        .into_inner()
+        .chain;
+    assert_eq!(blockchain.len(), 2);
+    for task in tasks {
+        task.abort();
+    }
+}
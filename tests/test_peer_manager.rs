@@ -0,0 +1,163 @@
+mod common;
+use blockchain::models::chain_params::ChainParams;
+use blockchain::models::peers::PeerManager;
+use blockchain::node::node_message_client::NodeMessageClient;
+use blockchain::node::{
+    GenerateBlocksRequest, GenerateTransactionRequest, GetPeerListRequest,
+    GetTransactionListRequest, NodeInfo, UpdateTransactionRequest,
+};
+use blockchain::{start, start_with_config};
+use std::time::{Duration, Instant};
+use tokio::net::TcpListener;
+use tonic::{Code, Request};
+use uuid::Uuid;
+
+/// Get the information of a peer listening on the given local port
+fn peer(port: u16) -> NodeInfo {
+    NodeInfo {
+        id: Uuid::new_v4().to_string(),
+        ip: "127.0.0.1".to_string(),
+        port: port as u32,
+    }
+}
+
+/// Ask a peer for its peer list through the peer manager
+async fn get_peer_list(manager: &PeerManager, peer: &NodeInfo) -> Result<Vec<NodeInfo>, Code> {
+    manager
+        .call(peer, |mut client| async move {
+            client
+                .get_peer_list(Request::new(GetPeerListRequest {}))
+                .await
+        })
+        .await
+        .map(|response| response.nodes)
+        .map_err(|status| status.code())
+}
+
+#[tokio::test]
+async fn test_unreachable_peer_backoff() {
+    let manager = PeerManager::new(
+        Duration::from_secs(1),
+        Duration::from_secs(1),
+        Duration::from_millis(200),
+        Duration::from_millis(400),
+    );
+    let peer = peer(50001);
+
+    // nothing listens on the port yet, so the peer is backed off and not called again right away
+    assert_eq!(get_peer_list(&manager, &peer).await, Err(Code::Unavailable));
+    assert_eq!(manager.failures(&peer), 1);
+    assert!(manager.is_backed_off(&peer));
+    assert_eq!(get_peer_list(&manager, &peer).await, Err(Code::Unavailable));
+    assert_eq!(manager.failures(&peer), 1);
+
+    // the backoff doubles with every failure in a row
+    tokio::time::sleep(Duration::from_millis(250)).await;
+    assert!(!manager.is_backed_off(&peer));
+    assert_eq!(get_peer_list(&manager, &peer).await, Err(Code::Unavailable));
+    assert_eq!(manager.failures(&peer), 2);
+    tokio::time::sleep(Duration::from_millis(250)).await;
+    assert!(manager.is_backed_off(&peer));
+
+    // the peer is called again once it is up and the backoff passed
+    let task = tokio::spawn(start(50001, None));
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert_eq!(get_peer_list(&manager, &peer).await.unwrap().len(), 1);
+    assert_eq!(manager.failures(&peer), 0);
+    assert!(!manager.is_backed_off(&peer));
+    task.abort();
+}
+
+#[tokio::test]
+async fn test_hung_peer_timeout() {
+    // the peer accepts the connections but never answers
+    let listener = TcpListener::bind("127.0.0.1:50000").await.unwrap();
+    let hung = tokio::spawn(async move {
+        let mut connections = Vec::new();
+        while let Ok((stream, _)) = listener.accept().await {
+            connections.push(stream);
+        }
+    });
+
+    let manager = PeerManager::new(
+        Duration::from_secs(1),
+        Duration::from_millis(300),
+        Duration::from_secs(1),
+        Duration::from_secs(1),
+    );
+    let peer = peer(50000);
+    let start = Instant::now();
+    assert_eq!(
+        get_peer_list(&manager, &peer).await,
+        Err(Code::DeadlineExceeded)
+    );
+    assert!(start.elapsed() < Duration::from_secs(1));
+    assert!(manager.is_backed_off(&peer));
+    hung.abort();
+}
+
+#[tokio::test]
+async fn test_dead_peer() {
+    let client = common::Client::new();
+    let nodes = [50002, 50003];
+    let mut tasks = Vec::new();
+    for (i, node) in nodes.into_iter().enumerate() {
+        let mut config = common::funded_config(node, nodes[..i].last().copied(), &client);
+        config.params = ChainParams::regtest();
+        tasks.push(tokio::spawn(start_with_config(config)));
+        tokio::time::sleep(Duration::from_millis(200)).await;
+    }
+    // the second node goes down without leaving the peer list of the first node
+    tasks.pop().unwrap().abort();
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    let mut grpc_client = NodeMessageClient::connect(format!("http://127.0.0.1:{}", nodes[0]))
+        .await
+        .expect("Failed to connect to node");
+    for _ in 0..2 {
+        let transaction = grpc_client
+            .generate_transaction(Request::new(GenerateTransactionRequest {
+                id: Uuid::new_v4().to_string(),
+                sender: client.public_key.clone(),
+                private_key: client.private_key.clone(),
+                receiver: "receiver".to_string(),
+                amount: 100,
+                fee: 1,
+                nonce: None,
+            }))
+            .await
+            .unwrap()
+            .into_inner()
+            .transaction
+            .unwrap();
+        // the transaction is pooled even though it cannot be relayed
+        let res = grpc_client
+            .update_client_transaction(Request::new(UpdateTransactionRequest {
+                transactions: vec![transaction],
+            }))
+            .await
+            .unwrap()
+            .into_inner();
+        assert!(res.success);
+    }
+    let pool = grpc_client
+        .get_transaction_list(Request::new(GetTransactionListRequest {}))
+        .await
+        .unwrap()
+        .into_inner()
+        .transactions;
+    assert_eq!(pool.len(), 2);
+
+    // and the block is mined even though it cannot be sent to the peer
+    let blocks = grpc_client
+        .generate_blocks(Request::new(GenerateBlocksRequest {
+            count: 1,
+            miner_address: String::new(),
+        }))
+        .await
+        .unwrap()
+        .into_inner()
+        .blocks;
+    assert_eq!(blocks[0].transactions[1], pool[0]);
+    tasks[0].abort();
+}
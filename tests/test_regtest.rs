@@ -0,0 +1,216 @@
+mod common;
+use blockchain::models::chain_params::MAX_GENERATED_BLOCKS;
+use blockchain::node::{
+    GenerateBlocksRequest, GenerateTransactionRequest, GetBlockchainRequest,
+    GetTransactionListRequest, UpdateBlockchainRequest, UpdateTransactionRequest,
+};
+use blockchain::{start, start_with_config};
+use std::time::Duration;
+use tonic::{Code, Request};
+use uuid::Uuid;
+
+#[tokio::test]
+async fn test_generate_blocks() {
+    let client = common::Client::new();
+    let mut tasks = Vec::new();
+    let nodes = [50000, 50001, 50002];
+    tasks.push(tokio::spawn(start_with_config(common::regtest_config(
+        nodes[0], None, &client,
+    ))));
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    for i in 1..nodes.len() {
+        tasks.push(tokio::spawn(start_with_config(common::regtest_config(
+            nodes[i],
+            Some(nodes[i - 1]),
+            &client,
+        ))));
+        tokio::time::sleep(Duration::from_millis(200)).await;
+    }
+
+    let mut grpc_client = common::connect(nodes[0]).await;
+    let transaction = grpc_client
+        .generate_transaction(Request::new(GenerateTransactionRequest {
+            id: Uuid::new_v4().to_string(),
+            sender: client.public_key.clone(),
+            private_key: client.private_key.clone(),
+            receiver: "receiver".to_string(),
+            amount: 100,
+            fee: 1,
+            nonce: None,
+        }))
+        .await
+        .unwrap()
+        .into_inner()
+        .transaction
+        .unwrap();
+    grpc_client
+        .update_client_transaction(Request::new(UpdateTransactionRequest {
+            transactions: vec![transaction.clone()],
+        }))
+        .await
+        .unwrap();
+
+    // wait for the transaction to be relayed to the peers
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    // one block with the pooled transaction is mined right away, and every node has it when the request returns
+    let blocks = common::connect(nodes[1])
+        .await
+        .generate_blocks(Request::new(GenerateBlocksRequest {
+            count: 1,
+            miner_address: "miner".to_string(),
+        }))
+        .await
+        .unwrap()
+        .into_inner()
+        .blocks;
+    assert_eq!(blocks.len(), 1);
+    assert_eq!(blocks[0].difficulty, 1);
+    assert_eq!(blocks[0].transactions[0].receiver, "miner");
+    assert_eq!(blocks[0].transactions[1], transaction);
+    for node in nodes {
+        let mut grpc_client = common::connect(node).await;
+        let chain = grpc_client
+            .get_blockchain(Request::new(GetBlockchainRequest {}))
+            .await
+            .unwrap()
+            .into_inner()
+            .chain;
+        assert_eq!(chain.len(), 2);
+        assert_eq!(chain[1], blocks[0]);
+        let transactions = grpc_client
+            .get_transaction_list(Request::new(GetTransactionListRequest {}))
+            .await
+            .unwrap()
+            .into_inner()
+            .transactions;
+        assert!(transactions.is_empty());
+    }
+
+    // empty blocks are mined as well, paying the node's miner address when none is given
+    let blocks = common::connect(nodes[2])
+        .await
+        .generate_blocks(Request::new(GenerateBlocksRequest {
+            count: 3,
+            miner_address: String::new(),
+        }))
+        .await
+        .unwrap()
+        .into_inner()
+        .blocks;
+    assert_eq!(blocks.len(), 3);
+    for node in nodes {
+        let chain = common::connect(node)
+            .await
+            .get_blockchain(Request::new(GetBlockchainRequest {}))
+            .await
+            .unwrap()
+            .into_inner()
+            .chain;
+        assert_eq!(chain.len(), 5);
+        assert_eq!(chain[2..], blocks);
+    }
+    for task in tasks {
+        task.abort();
+    }
+}
+
+#[tokio::test]
+async fn test_regtest_longer_chain() {
+    let client = common::Client::new();
+    let mut tasks = Vec::new();
+    let nodes = [50003, 50004];
+    for node in nodes {
+        tasks.push(tokio::spawn(start_with_config(common::regtest_config(
+            node, None, &client,
+        ))));
+    }
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    // the nodes do not know each other, so they mine different chains
+    for (node, count) in nodes.into_iter().zip([1, 2]) {
+        common::connect(node)
+            .await
+            .generate_blocks(Request::new(GenerateBlocksRequest {
+                count,
+                miner_address: node.to_string(),
+            }))
+            .await
+            .unwrap();
+    }
+    let longer_chain = common::connect(nodes[1])
+        .await
+        .get_blockchain(Request::new(GetBlockchainRequest {}))
+        .await
+        .unwrap()
+        .into_inner()
+        .chain;
+
+    // the first node switches to the longer chain
+    let mut grpc_client = common::connect(nodes[0]).await;
+    let response = grpc_client
+        .update_blockchain(Request::new(UpdateBlockchainRequest {
+            blocks: longer_chain.clone(),
+            node: None,
+        }))
+        .await
+        .unwrap()
+        .into_inner();
+    assert!(response.success);
+    let chain = grpc_client
+        .get_blockchain(Request::new(GetBlockchainRequest {}))
+        .await
+        .unwrap()
+        .into_inner()
+        .chain;
+    assert_eq!(chain, longer_chain);
+    for task in tasks {
+        task.abort();
+    }
+}
+
+#[tokio::test]
+async fn test_generate_blocks_refused() {
+    // a node of a network that mines on its own does not generate blocks on request
+    let task = tokio::spawn(start(50005, None));
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    let status = common::connect(50005)
+        .await
+        .generate_blocks(Request::new(GenerateBlocksRequest {
+            count: 1,
+            miner_address: String::new(),
+        }))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::FailedPrecondition);
+    task.abort();
+}
+
+#[tokio::test]
+async fn test_generate_blocks_limit() {
+    let client = common::Client::new();
+    let task = tokio::spawn(start_with_config(common::regtest_config(
+        50006, None, &client,
+    )));
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    // one request cannot hold the node mining an unbounded number of blocks
+    let status = common::connect(50006)
+        .await
+        .generate_blocks(Request::new(GenerateBlocksRequest {
+            count: MAX_GENERATED_BLOCKS + 1,
+            miner_address: String::new(),
+        }))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::InvalidArgument);
+    let chain = common::connect(50006)
+        .await
+        .get_blockchain(Request::new(GetBlockchainRequest {}))
+        .await
+        .unwrap()
+        .into_inner()
+        .chain;
+    assert_eq!(chain.len(), 1);
+    task.abort();
+}
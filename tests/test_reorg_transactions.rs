@@ -0,0 +1,151 @@
+mod common;
+use blockchain::node::node_message_client::NodeMessageClient;
+use blockchain::node::GenerateBlocksRequest;
+use blockchain::node::GenerateTransactionRequest;
+use blockchain::node::GetBlockchainRequest;
+use blockchain::node::GetTransactionListRequest;
+use blockchain::node::UpdateBlockchainRequest;
+use blockchain::node::UpdateTransactionRequest;
+use blockchain::start_with_config;
+use std::time::Duration;
+use tonic::Request;
+use uuid::Uuid;
+
+#[tokio::test]
+async fn test_reorg_transactions() {
+    let mut tasks = Vec::new();
+
+    // Create two clients, the second one sends the transactions of the second node
+    // so they do not use the nonce of the transaction of the first node
+    let client = common::Client::new();
+    let other = common::Client::new();
+
+    // start two nodes without connecting them, so they will have different blockchains
+    let nodes = vec![50000, 50001];
+    for node in &nodes {
+        let mut config = common::regtest_config(*node, None, &client);
+        config.allocations.push((other.public_key.clone(), 1000));
+        tasks.push(tokio::spawn(start_with_config(config)));
+        tokio::time::sleep(Duration::from_millis(200)).await;
+    }
+
+    let mut grpc_client = NodeMessageClient::connect(format!("http://127.0.0.1:{}", nodes[0]))
+        .await
+        .expect("Failed to connect to node");
+    let mut transactions = Vec::new();
+    for (sender, receiver, amount, nonce) in [
+        (&client, "receiver1", 100, 0),
+        (&other, "receiver2", 200, 0),
+        (&other, "receiver3", 300, 1),
+    ] {
+        let transaction = grpc_client
+            .generate_transaction(Request::new(GenerateTransactionRequest {
+                id: Uuid::new_v4().to_string(),
+                sender: sender.public_key.clone(),
+                private_key: sender.private_key.clone(),
+                receiver: receiver.to_string(),
+                amount,
+                fee: 1,
+                nonce: Some(nonce),
+            }))
+            .await
+            .unwrap()
+            .into_inner()
+            .transaction
+            .unwrap();
+        transactions.push(transaction);
+    }
+
+    // the first node mines one block with the first transaction
+    grpc_client
+        .update_client_transaction(Request::new(UpdateTransactionRequest {
+            transactions: vec![transactions[0].clone()],
+        }))
+        .await
+        .unwrap();
+    grpc_client
+        .generate_blocks(Request::new(GenerateBlocksRequest {
+            count: 1,
+            miner_address: String::new(),
+        }))
+        .await
+        .unwrap();
+
+    // the second node mines two blocks with the other transactions, so its chain has more work
+    let mut grpc_client = NodeMessageClient::connect(format!("http://127.0.0.1:{}", nodes[1]))
+        .await
+        .expect("Failed to connect to node");
+    for transaction in &transactions[1..] {
+        grpc_client
+            .update_client_transaction(Request::new(UpdateTransactionRequest {
+                transactions: vec![transaction.clone()],
+            }))
+            .await
+            .unwrap();
+        grpc_client
+            .generate_blocks(Request::new(GenerateBlocksRequest {
+                count: 1,
+                miner_address: String::new(),
+            }))
+            .await
+            .unwrap();
+    }
+    let bc2 = grpc_client
+        .get_blockchain(Request::new(GetBlockchainRequest {}))
+        .await
+        .unwrap()
+        .into_inner()
+        .chain;
+    assert_eq!(bc2.len(), 3);
+
+    // the first node switches to the chain of the second node, and its block is disconnected
+    let mut grpc_client = NodeMessageClient::connect(format!("http://127.0.0.1:{}", nodes[0]))
+        .await
+        .expect("Failed to connect to node");
+    let bc1 = grpc_client
+        .get_blockchain(Request::new(GetBlockchainRequest {}))
+        .await
+        .unwrap()
+        .into_inner()
+        .chain;
+    assert_eq!(bc1.len(), 2);
+    assert_eq!(bc1[1].transactions[1], transactions[0]);
+    let response = grpc_client
+        .update_blockchain(Request::new(UpdateBlockchainRequest {
+            blocks: bc2.clone(),
+            node: None,
+        }))
+        .await
+        .unwrap()
+        .into_inner();
+    assert!(response.success);
+
+    // the transaction of the disconnected block goes back to the pool, and is mined again
+    let pool = grpc_client
+        .get_transaction_list(Request::new(GetTransactionListRequest {}))
+        .await
+        .unwrap()
+        .into_inner()
+        .transactions;
+    assert_eq!(pool, transactions[..1]);
+    grpc_client
+        .generate_blocks(Request::new(GenerateBlocksRequest {
+            count: 1,
+            miner_address: String::new(),
+        }))
+        .await
+        .unwrap();
+
+    let bc3 = grpc_client
+        .get_blockchain(Request::new(GetBlockchainRequest {}))
+        .await
+        .unwrap()
+        .into_inner()
+        .chain;
+    assert_eq!(bc3.len(), 4);
+    assert_eq!(bc3[..3], bc2);
+    assert_eq!(bc3[3].transactions[1..], transactions[..1]);
+    for task in tasks {
+        task.abort();
+    }
+}
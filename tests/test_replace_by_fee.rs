@@ -0,0 +1,163 @@
+mod common;
+use blockchain::models::chain_params::ChainParams;
+use blockchain::models::ledger::Ledger;
+use blockchain::models::mempool::{replacement_fee, Mempool, RejectReason};
+use blockchain::node::{
+    Block, GenerateBlocksRequest, GenerateTransactionRequest, GetBlockchainRequest,
+    GetTransactionListRequest, UpdateTransactionRequest,
+};
+use blockchain::start_with_config;
+use std::time::Duration;
+use tonic::Request;
+use uuid::Uuid;
+
+#[test]
+fn test_replacement_fee() {
+    // a replacement pays at least 10% more, and at least one more
+    assert_eq!(replacement_fee(0), 1);
+    assert_eq!(replacement_fee(5), 6);
+    assert_eq!(replacement_fee(50), 55);
+    assert_eq!(replacement_fee(i32::MAX), i32::MAX);
+}
+
+#[test]
+fn test_replace_by_fee() {
+    let ledger = Ledger::new(&[("alice".to_string(), 100)]);
+    let mut mempool = Mempool::default();
+    let original = common::transaction("payment", "alice", 0, 50, 10);
+    let other = common::transaction("other", "alice", 1, 30, 1);
+    assert_eq!(mempool.insert(original.clone(), &ledger), Ok(None));
+    assert_eq!(mempool.insert(other.clone(), &ledger), Ok(None));
+
+    // resending the same transaction is a duplicate, and a replacement must pay the replacement fee
+    assert_eq!(
+        mempool.insert(original.clone(), &ledger),
+        Err(RejectReason::Duplicate)
+    );
+    let mut cheap = common::transaction("payment", "alice", 0, 50, 10);
+    cheap.receiver = "another receiver".to_string();
+    cheap.hash = cheap.compute_hash();
+    assert_eq!(
+        mempool.insert(cheap, &ledger),
+        Err(RejectReason::ReplacementFeeTooLow)
+    );
+
+    // the replaced transaction does not count against the balance of the replacement, but the other pending ones do
+    assert_eq!(
+        mempool.insert(common::transaction("payment", "alice", 0, 59, 11), &ledger),
+        Err(RejectReason::InsufficientBalance)
+    );
+    // the replacement only needs the nonce of the replaced transaction, not its id
+    let bumped = common::transaction("bump", "alice", 0, 50, 11);
+    assert_eq!(
+        mempool.insert(bumped.clone(), &ledger),
+        Ok(Some(original.clone()))
+    );
+    // the replacement keeps the place of the replaced transaction
+    assert_eq!(mempool.to_vec(), [bumped.clone(), other]);
+    assert_eq!(mempool.get("bump"), Some(&bumped));
+    assert_eq!(mempool.get("payment"), None);
+    assert!(!mempool.contains(&original));
+
+    // once the original transaction is mined, its replacement leaves the pool
+    let block = Block {
+        transactions: vec![original],
+        ..Block::default()
+    };
+    mempool.remove_included(&block.transactions);
+    assert_eq!(mempool.get("bump"), None);
+    assert_eq!(mempool.len(), 1);
+}
+
+#[tokio::test]
+async fn test_replacement_relay() {
+    let client = common::Client::new();
+    let mut tasks = Vec::new();
+    let nodes = [50000, 50001];
+    for (i, node) in nodes.into_iter().enumerate() {
+        let mut config = common::funded_config(node, nodes[..i].last().copied(), &client);
+        config.params = ChainParams::regtest();
+        tasks.push(tokio::spawn(start_with_config(config)));
+        tokio::time::sleep(Duration::from_millis(200)).await;
+    }
+
+    let mut grpc_client = common::connect(nodes[0]).await;
+    let mut transactions = Vec::new();
+    for fee in [1, 2] {
+        let transaction = grpc_client
+            .generate_transaction(Request::new(GenerateTransactionRequest {
+                id: Uuid::new_v4().to_string(),
+                sender: client.public_key.clone(),
+                private_key: client.private_key.clone(),
+                receiver: "receiver".to_string(),
+                amount: 100,
+                fee,
+                nonce: Some(0),
+            }))
+            .await
+            .unwrap()
+            .into_inner()
+            .transaction
+            .unwrap();
+        transactions.push(transaction);
+    }
+
+    // the node that receives the fee bump from the client relays it to its peers
+    for transaction in &transactions {
+        let res = grpc_client
+            .update_client_transaction(Request::new(UpdateTransactionRequest {
+                transactions: vec![transaction.clone()],
+            }))
+            .await
+            .unwrap()
+            .into_inner();
+        assert!(res.success);
+    }
+    // wait for the transactions to be relayed to the peers
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    for node in nodes {
+        let pool = common::connect(node)
+            .await
+            .get_transaction_list(Request::new(GetTransactionListRequest {}))
+            .await
+            .unwrap()
+            .into_inner()
+            .transactions;
+        assert_eq!(pool, transactions[1..]);
+    }
+
+    // the original transaction cannot come back once replaced
+    let res = grpc_client
+        .update_client_transaction(Request::new(UpdateTransactionRequest {
+            transactions: transactions[..1].to_vec(),
+        }))
+        .await
+        .unwrap()
+        .into_inner();
+    assert!(!res.success);
+    assert_eq!(
+        res.rejected[0].reason,
+        RejectReason::ReplacementFeeTooLow.to_string()
+    );
+
+    // the replacement is mined
+    common::connect(nodes[1])
+        .await
+        .generate_blocks(Request::new(GenerateBlocksRequest {
+            count: 1,
+            miner_address: String::new(),
+        }))
+        .await
+        .unwrap();
+    let chain = grpc_client
+        .get_blockchain(Request::new(GetBlockchainRequest {}))
+        .await
+        .unwrap()
+        .into_inner()
+        .chain;
+    assert_eq!(chain[1].transactions[1..], transactions[1..]);
+    for task in tasks {
+        task.abort();
+    }
+}
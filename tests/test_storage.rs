@@ -0,0 +1,129 @@
+mod common;
+use blockchain::models::blockchain::Blockchain;
+use blockchain::models::node::Node;
+use blockchain::models::storage::BlockStore;
+use blockchain::node::node_message_client::NodeMessageClient;
+use blockchain::node::GenerateBlocksRequest;
+use blockchain::node::GenerateTransactionRequest;
+use blockchain::node::GetBlockchainRequest;
+use blockchain::node::UpdateTransactionRequest;
+use blockchain::start_with_config;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::time::Duration;
+use tonic::Request;
+use uuid::Uuid;
+
+#[tokio::test]
+async fn test_storage() {
+    let client = common::Client::new();
+    let data_dir = std::env::temp_dir().join(Uuid::new_v4().to_string());
+    let mut config = common::regtest_config(50000, None, &client);
+    config.data_dir = Some(data_dir.clone());
+    let task = tokio::spawn(start_with_config(config.clone()));
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    let mut grpc_client = NodeMessageClient::connect("http://127.0.0.1:50000")
+        .await
+        .expect("Failed to connect to node");
+    let mut transactions = Vec::new();
+    for (nonce, amount) in [100, 200].into_iter().enumerate() {
+        let transaction = grpc_client
+            .generate_transaction(Request::new(GenerateTransactionRequest {
+                id: Uuid::new_v4().to_string(),
+                sender: client.public_key.clone(),
+                private_key: client.private_key.clone(),
+                receiver: "receiver".to_string(),
+                amount,
+                fee: 1,
+                nonce: Some(nonce as u64),
+            }))
+            .await
+            .unwrap()
+            .into_inner()
+            .transaction
+            .unwrap();
+        transactions.push(transaction);
+    }
+    grpc_client
+        .update_client_transaction(Request::new(UpdateTransactionRequest {
+            transactions: transactions[..1].to_vec(),
+        }))
+        .await
+        .unwrap();
+
+    // the transaction is pooled when the request returns, so the block with it is mined right away
+    grpc_client
+        .generate_blocks(Request::new(GenerateBlocksRequest {
+            count: 1,
+            miner_address: String::new(),
+        }))
+        .await
+        .unwrap();
+    let chain = grpc_client
+        .get_blockchain(Request::new(GetBlockchainRequest {}))
+        .await
+        .unwrap()
+        .into_inner()
+        .chain;
+    assert_eq!(chain.len(), 2);
+    task.abort();
+
+    // a restarted node loads the chain and the balances from the data directory
+    let node = Node::new(&config);
+    let mut blockchain = node.blockchain.lock().await;
+    assert_eq!(blockchain.chain, chain);
+    assert_eq!(blockchain.ledger.balance(&client.public_key), 1000 - 101);
+    assert!(blockchain.add_transaction(transactions[1].clone()).is_ok());
+    // the node writes the changed pool in the background, which is done here without a running node
+    blockchain.transactions_write().unwrap().write().unwrap();
+    assert!(blockchain.transactions_write().is_none());
+    drop(blockchain);
+
+    // a crash while appending a block leaves garbage after the indexed blocks and a temporary index
+    let mut blocks = OpenOptions::new()
+        .append(true)
+        .open(data_dir.join("blocks.dat"))
+        .unwrap();
+    blocks.write_all(&[42; 100]).unwrap();
+    std::fs::write(data_dir.join("index.tmp"), "0 garbage").unwrap();
+
+    let node = Node::new(&config);
+    let blockchain = node.blockchain.lock().await;
+    assert_eq!(blockchain.chain, chain);
+    assert_eq!(blockchain.mempool.to_vec(), transactions[1..]);
+    let mut storage = BlockStore::open(&data_dir).unwrap();
+    assert_eq!(storage.height(), 2);
+    assert_eq!(storage.read_block(1).unwrap().as_ref(), chain.get(1));
+    assert_eq!(storage.read_block(0).unwrap().as_ref(), chain.first());
+    std::fs::remove_dir_all(&data_dir).unwrap();
+}
+
+#[test]
+fn test_storage_write_failure() {
+    let data_dir = std::env::temp_dir().join(Uuid::new_v4().to_string());
+    let chain = common::mine_chain(2, "miner");
+    let mut blockchain = Blockchain::new();
+    blockchain
+        .open_storage(BlockStore::open(&data_dir).unwrap())
+        .unwrap();
+    assert!(blockchain.push_block(chain[1].clone()).unwrap());
+
+    // the index cannot be replaced while a directory takes the place of its temporary file
+    std::fs::create_dir(data_dir.join("index.tmp")).unwrap();
+    assert!(blockchain.push_block(chain[2].clone()).is_err());
+    assert!(blockchain.replace_chain(chain.clone()).is_err());
+
+    // the chain and the balances stay as they are on disk
+    assert_eq!(blockchain.chain, chain[..2]);
+    assert_eq!(blockchain.ledger.balance("miner"), 50);
+    assert_eq!(BlockStore::open(&data_dir).unwrap().height(), 2);
+
+    std::fs::remove_dir(data_dir.join("index.tmp")).unwrap();
+    assert!(blockchain.push_block(chain[2].clone()).unwrap());
+    assert_eq!(
+        BlockStore::open(&data_dir).unwrap().read_chain().unwrap(),
+        chain
+    );
+    std::fs::remove_dir_all(&data_dir).unwrap();
+}
@@ -3,7 +3,7 @@ use blockchain::node::node_message_client::NodeMessageClient;
 use blockchain::node::GenerateTransactionRequest;
 use blockchain::node::GetTransactionListRequest;
 use blockchain::node::UpdateTransactionRequest;
-use blockchain::start;
+use blockchain::start_with_config;
 use std::time::Duration;
 use tonic::Request;
 use uuid::Uuid;
@@ -12,17 +12,28 @@ use uuid::Uuid;
 async fn test_submit_transaction_10_percents() {
     let mut tasks = Vec::new();
 
+    // Create a client and create public key and private key
+    let client = common::Client::new();
+
     let nodes = vec![50000, 50001, 50002];
-    tasks.push(tokio::spawn(start(nodes[0], None)));
+    tasks.push(tokio::spawn(start_with_config(common::funded_config(
+        nodes[0], None, &client,
+    ))));
     tokio::time::sleep(Duration::from_millis(200)).await;
-    tasks.push(tokio::spawn(start(nodes[1], Some(nodes[0]))));
+    tasks.push(tokio::spawn(start_with_config(common::funded_config(
+        nodes[1],
+        Some(nodes[0]),
+        &client,
+    ))));
     tokio::time::sleep(Duration::from_millis(200)).await;
-    tasks.push(tokio::spawn(start(nodes[2], Some(nodes[1]))));
+    tasks.push(tokio::spawn(start_with_config(common::funded_config(
+        nodes[2],
+        Some(nodes[1]),
+        &client,
+    ))));
     tokio::time::sleep(Duration::from_millis(200)).await;
     // Wait for the nodes to start
 
-    // Create a client and create public key and private key
-    let client = common::Client::new();
     let mut grpc_client = NodeMessageClient::connect(format!("http://127.0.0.1:{}", nodes[0]))
         .await
         .expect("Failed to connect to node");
@@ -33,6 +44,7 @@ async fn test_submit_transaction_10_percents() {
         receiver: "receiver".to_string(),
         amount: 100,
         fee: 1,
+        nonce: None,
     }));
 
     let transaction = res.await.unwrap().into_inner().transaction.unwrap();
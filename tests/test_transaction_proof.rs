@@ -0,0 +1,137 @@
+mod common;
+use blockchain::models::blockchain::sha_hash;
+use blockchain::models::merkle::{
+    merkle_branch, merkle_root, verify_merkle_branch, verify_transaction_proof,
+};
+use blockchain::node::node_message_client::NodeMessageClient;
+use blockchain::node::BlockHeader;
+use blockchain::node::GenerateBlocksRequest;
+use blockchain::node::GenerateTransactionRequest;
+use blockchain::node::GetBlockchainRequest;
+use blockchain::node::GetTransactionProofRequest;
+use blockchain::node::UpdateTransactionRequest;
+use blockchain::start_with_config;
+use std::time::Duration;
+use tonic::{Code, Request};
+use uuid::Uuid;
+
+#[test]
+fn test_merkle_branch() {
+    for size in 1..=7 {
+        let hashes: Vec<String> = (0..size).map(|i| sha_hash(&i.to_string())).collect();
+        let root = merkle_root(&hashes);
+        for (index, hash) in hashes.iter().enumerate() {
+            let branch = merkle_branch(&hashes, index);
+            assert!(verify_merkle_branch(hash, index, &branch, &root));
+            // the bits of the index above the depth of the branch are not ignored
+            assert!(!verify_merkle_branch(
+                hash,
+                index + (1 << branch.len()),
+                &branch,
+                &root
+            ));
+            if size > 1 {
+                assert!(!verify_merkle_branch(
+                    &sha_hash("other"),
+                    index,
+                    &branch,
+                    &root
+                ));
+            }
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_transaction_proof() {
+    let client = common::Client::new();
+    let task = tokio::spawn(start_with_config(common::regtest_config(
+        50000, None, &client,
+    )));
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    let mut grpc_client = NodeMessageClient::connect("http://127.0.0.1:50000")
+        .await
+        .expect("Failed to connect to node");
+    let transaction = grpc_client
+        .generate_transaction(Request::new(GenerateTransactionRequest {
+            id: Uuid::new_v4().to_string(),
+            sender: client.public_key.clone(),
+            private_key: client.private_key.clone(),
+            receiver: "receiver".to_string(),
+            amount: 100,
+            fee: 1,
+            nonce: None,
+        }))
+        .await
+        .unwrap()
+        .into_inner()
+        .transaction
+        .unwrap();
+    grpc_client
+        .update_client_transaction(Request::new(UpdateTransactionRequest {
+            transactions: vec![transaction.clone()],
+        }))
+        .await
+        .unwrap();
+
+    // the transaction is pooled when the request returns, so the block with it is mined right away
+    grpc_client
+        .generate_blocks(Request::new(GenerateBlocksRequest {
+            count: 1,
+            miner_address: String::new(),
+        }))
+        .await
+        .unwrap();
+
+    let proof = grpc_client
+        .get_transaction_proof(Request::new(GetTransactionProofRequest {
+            transaction_hash: transaction.hash.clone(),
+        }))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(proof.transaction, Some(transaction.clone()));
+    assert_eq!(proof.confirmations, 1);
+
+    // the client verifies the proof with the block headers only
+    let headers: Vec<BlockHeader> = grpc_client
+        .get_blockchain(Request::new(GetBlockchainRequest {}))
+        .await
+        .unwrap()
+        .into_inner()
+        .chain
+        .iter()
+        .map(|block| block.header())
+        .collect();
+    assert_eq!(
+        verify_transaction_proof(&headers, &proof, &transaction.hash),
+        Some(1)
+    );
+
+    // a valid proof of another transaction does not confirm the transaction asked about
+    assert_eq!(
+        verify_transaction_proof(&headers, &proof, &sha_hash("other")),
+        None
+    );
+
+    // a modified transaction does not match the proof
+    let mut tampered = proof.clone();
+    let tampered_transaction = tampered.transaction.as_mut().unwrap();
+    tampered_transaction.amount = 1000;
+    tampered_transaction.hash = tampered_transaction.compute_hash();
+    let tampered_hash = tampered_transaction.hash.clone();
+    assert_eq!(
+        verify_transaction_proof(&headers, &tampered, &tampered_hash),
+        None
+    );
+
+    let status = grpc_client
+        .get_transaction_proof(Request::new(GetTransactionProofRequest {
+            transaction_hash: sha_hash("other"),
+        }))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::NotFound);
+    task.abort();
+}